@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Host-side tests for kernel modules that do not depend on the target.
+//!
+//! The modules are compiled straight from the kernel's source tree, together with their tests.
+//!
+//! ```console
+//! $ cargo test --manifest-path host_tests/Cargo.toml
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+
+// Reexported like in the kernel's `time` module, where `timeout_queue` expects it.
+use instant::Instant;
+
+#[allow(dead_code)]
+#[path = "../../src/console/line_discipline.rs"]
+mod line_discipline;
+
+#[allow(dead_code)]
+#[path = "../../src/console/log_buffer/inner.rs"]
+mod log_buffer;
+
+#[allow(dead_code)]
+#[path = "../../src/console/line_edit.rs"]
+mod line_edit;
+
+#[allow(dead_code)]
+#[path = "../../src/time/counter.rs"]
+mod counter;
+
+#[allow(dead_code)]
+#[path = "../../src/time/instant.rs"]
+mod instant;
+
+#[allow(dead_code)]
+#[path = "../../src/time/timeout_queue.rs"]
+mod timeout_queue;
+
+#[allow(dead_code)]
+#[path = "../../src/synchronization/ticket_lock.rs"]
+mod ticket_lock;
+
+#[allow(dead_code)]
+#[path = "../../src/driver/dependency_sort.rs"]
+mod dependency_sort;
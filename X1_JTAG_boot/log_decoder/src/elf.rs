@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Just enough ELF parsing to extract a section from the kernel.
+//!
+//! Only 64-bit little endian files are supported, which is what the `AArch64` kernel is.
+
+use std::ops::Range;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn read_u16(elf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        elf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(elf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        elf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(elf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        elf.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Return the file range of the contents of the section header at `offset`.
+fn section_range(elf: &[u8], offset: usize) -> Option<Range<usize>> {
+    let start = read_u64(elf, offset + 0x18)? as usize;
+    let size = read_u64(elf, offset + 0x20)? as usize;
+
+    Some(start..start.checked_add(size)?)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return the contents of the section called `name`.
+pub fn find_section<'a>(elf: &'a [u8], name: &str) -> Result<&'a [u8], String> {
+    if !elf.starts_with(ELF_MAGIC) || elf.get(4) != Some(&ELFCLASS64) {
+        return Err("Not a 64-bit ELF file".into());
+    }
+    if elf.get(5) != Some(&ELFDATA2LSB) {
+        return Err("Not a little endian ELF file".into());
+    }
+
+    let malformed = || String::from("Malformed ELF file");
+
+    let shoff = read_u64(elf, 0x28).ok_or_else(malformed)? as usize;
+    let shentsize = read_u16(elf, 0x3a).ok_or_else(malformed)? as usize;
+    let shnum = read_u16(elf, 0x3c).ok_or_else(malformed)? as usize;
+    let shstrndx = read_u16(elf, 0x3e).ok_or_else(malformed)? as usize;
+
+    let header = |index: usize| shoff + index * shentsize;
+    let names = section_range(elf, header(shstrndx))
+        .and_then(|x| elf.get(x))
+        .ok_or_else(malformed)?;
+
+    for index in 0..shnum {
+        let name_offset = read_u32(elf, header(index)).ok_or_else(malformed)? as usize;
+        let section_name = names
+            .get(name_offset..)
+            .and_then(|x| x.split(|&b| b == 0).next())
+            .ok_or_else(malformed)?;
+
+        if section_name == name.as_bytes() {
+            return section_range(elf, header(index))
+                .and_then(|x| elf.get(x))
+                .ok_or_else(malformed);
+        }
+    }
+
+    Err(format!(
+        "Section {} not found. Was the kernel built with BINARY_LOG=1?",
+        name
+    ))
+}
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Formatting of decoded log arguments.
+//!
+//! Supports what the kernel lets through its compile time check of format strings: positional
+//! placeholders, and fill, alignment, sign, `#`, zero padding, width, precision and the types `x`,
+//! `X`, `o` and `b` in the format spec.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A decoded log argument.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    /// A signed integer, and the size of its type in bytes.
+    Signed(i64, u8),
+    Bool(bool),
+    Char(char),
+    Str(String),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The parsed part after the colon of a format placeholder.
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    plus: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    ty: Option<char>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Spec {
+    fn parse(spec: &str) -> Self {
+        let mut result = Spec::default();
+        let mut chars = spec.chars().peekable();
+        let is_align = |c: char| matches!(c, '<' | '^' | '>');
+
+        let mut lookahead = spec.chars();
+        match (lookahead.next(), lookahead.next()) {
+            (Some(fill), Some(align)) if is_align(align) => {
+                result.fill = Some(fill);
+                result.align = Some(align);
+                chars.nth(1);
+            }
+            (Some(align), _) if is_align(align) => {
+                result.align = Some(align);
+                chars.next();
+            }
+            _ => (),
+        }
+
+        result.plus = chars.next_if_eq(&'+').is_some();
+        chars.next_if_eq(&'-');
+        result.alternate = chars.next_if_eq(&'#').is_some();
+        result.zero = chars.next_if_eq(&'0').is_some();
+
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            result.width = result.width * 10 + digit as usize;
+            chars.next();
+        }
+
+        if chars.next_if_eq(&'.').is_some() {
+            let mut precision = 0;
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                precision = precision * 10 + digit as usize;
+                chars.next();
+            }
+            result.precision = Some(precision);
+        }
+
+        result.ty = chars.next();
+
+        result
+    }
+
+    /// Format an integer, given as sign and magnitude. Returns the prefix, which is the sign and
+    /// the radix marker, and the digits.
+    fn integer(&self, negative: bool, magnitude: u64) -> (String, String) {
+        let (digits, marker) = match self.ty {
+            Some('x') => (format!("{:x}", magnitude), "0x"),
+            Some('X') => (format!("{:X}", magnitude), "0x"),
+            Some('o') => (format!("{:o}", magnitude), "0o"),
+            Some('b') => (format!("{:b}", magnitude), "0b"),
+            _ => (magnitude.to_string(), ""),
+        };
+
+        let mut prefix = String::new();
+        if negative {
+            prefix.push('-');
+        } else if self.plus {
+            prefix.push('+');
+        }
+        if self.alternate {
+            prefix.push_str(marker);
+        }
+
+        (prefix, digits)
+    }
+
+    fn apply(&self, value: &Value) -> String {
+        let is_radix = matches!(self.ty, Some('x' | 'X' | 'o' | 'b'));
+
+        let (prefix, text) = match *value {
+            Value::Unsigned(x) => self.integer(false, x),
+            // Like `core::fmt`, print negative values in two's complement of the type's width.
+            Value::Signed(x, size) if is_radix && x < 0 => {
+                let mask = u64::MAX >> (64 - 8 * u32::from(size.clamp(1, 8)));
+                self.integer(false, x as u64 & mask)
+            }
+            Value::Signed(x, _) => self.integer(x < 0, x.unsigned_abs()),
+            Value::Bool(x) => (String::new(), x.to_string()),
+            Value::Char(x) => (String::new(), x.to_string()),
+            Value::Str(ref x) => match self.precision {
+                Some(precision) => (String::new(), x.chars().take(precision).collect()),
+                None => (String::new(), x.clone()),
+            },
+        };
+        let is_numeric = matches!(value, Value::Unsigned(_) | Value::Signed(..));
+
+        let len = prefix.chars().count() + text.chars().count();
+        if len >= self.width {
+            return prefix + &text;
+        }
+        let padding = self.width - len;
+
+        // Zero padding goes between the prefix and the digits, like in `core::fmt`.
+        if self.zero && is_numeric {
+            return format!("{}{}{}", prefix, "0".repeat(padding), text);
+        }
+
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let default_align = if is_numeric { '>' } else { '<' };
+        let (left, right) = match self.align.unwrap_or(default_align) {
+            '>' => (padding, 0),
+            '^' => (padding / 2, padding - padding / 2),
+            _ => (0, padding),
+        };
+
+        format!(
+            "{}{}{}{}",
+            fill.repeat(left),
+            prefix,
+            text,
+            fill.repeat(right)
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Substitute the placeholders of `format_string` with `args`.
+///
+/// If `truncated` is set, the frame did not have room for all arguments, and the missing ones are
+/// marked as such.
+pub fn format_message(format_string: &str, args: &[Value], truncated: bool) -> String {
+    let mut result = String::new();
+    let mut next_arg = 0;
+    let mut chars = format_string.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (arg, spec) = placeholder.split_once(':').unwrap_or((&placeholder, ""));
+
+                // Explicit positions do not advance the implicit one, like in `core::fmt`.
+                let index = match arg.parse() {
+                    Ok(index) => index,
+                    Err(_) => {
+                        next_arg += 1;
+                        next_arg - 1
+                    }
+                };
+
+                match args.get(index) {
+                    Some(value) => result.push_str(&Spec::parse(spec).apply(value)),
+                    None if truncated => result.push_str("<truncated>"),
+                    None => result.push_str("<missing argument>"),
+                }
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(format_string: &str, value: Value) -> String {
+        format_message(format_string, &[value], false)
+    }
+
+    #[test]
+    fn width_fill_and_alignment() {
+        assert_eq!(fmt("{:5}", Value::Unsigned(42)), format!("{:5}", 42));
+        assert_eq!(fmt("{:<5}", Value::Unsigned(42)), format!("{:<5}", 42));
+        assert_eq!(
+            fmt("{:*^7}", Value::Str("ab".into())),
+            format!("{:*^7}", "ab")
+        );
+        assert_eq!(fmt("{:5}", Value::Char('c')), format!("{:5}", 'c'));
+        assert_eq!(fmt("{:06}", Value::Signed(-42, 4)), format!("{:06}", -42));
+        assert_eq!(fmt("{:+}", Value::Signed(42, 4)), format!("{:+}", 42));
+    }
+
+    #[test]
+    fn radix() {
+        assert_eq!(fmt("{:x}", Value::Unsigned(255)), format!("{:x}", 255));
+        assert_eq!(fmt("{:#X}", Value::Unsigned(255)), format!("{:#X}", 255));
+        assert_eq!(
+            fmt("{:#010x}", Value::Unsigned(255)),
+            format!("{:#010x}", 255)
+        );
+        assert_eq!(fmt("{:#o}", Value::Unsigned(8)), format!("{:#o}", 8));
+        assert_eq!(fmt("{:b}", Value::Unsigned(5)), format!("{:b}", 5));
+
+        // Negative values depend on the width of the type.
+        assert_eq!(fmt("{:x}", Value::Signed(-1, 1)), format!("{:x}", -1i8));
+        assert_eq!(fmt("{:x}", Value::Signed(-2, 4)), format!("{:x}", -2i32));
+        assert_eq!(fmt("{:b}", Value::Signed(-1, 8)), format!("{:b}", -1i64));
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(
+            fmt("{:.2}", Value::Str("abc".into())),
+            format!("{:.2}", "abc")
+        );
+        assert_eq!(
+            fmt("{:>5.1}", Value::Str("abc".into())),
+            format!("{:>5.1}", "abc")
+        );
+    }
+
+    #[test]
+    fn bool_is_not_a_number() {
+        assert_eq!(fmt("{}", Value::Bool(true)), "true");
+        assert_eq!(fmt("{:>6}", Value::Bool(false)), format!("{:>6}", false));
+    }
+
+    #[test]
+    fn positional_arguments() {
+        let args = [Value::Unsigned(1), Value::Unsigned(2)];
+
+        assert_eq!(
+            format_message("{1} {} {0} {}", &args, false),
+            format!("{1} {} {0} {}", 1, 2)
+        );
+    }
+
+    #[test]
+    fn missing_arguments() {
+        assert_eq!(
+            format_message("{} {}", &[], false),
+            "<missing argument> <missing argument>"
+        );
+        assert_eq!(format_message("{{{}}}", &[], true), "{<truncated>}");
+    }
+}
@@ -0,0 +1,490 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Decoder for the binary log encoding of the kernel.
+//!
+//! Reads the kernel output from stdin and writes it to stdout, with log frames turned back into
+//! text. The format strings are taken from the `.log_strings` section of the kernel ELF. The wire
+//! format is described in the kernel's `src/print/binary.rs`.
+//!
+//! # Usage
+//!
+//! ```console
+//! $ make BINARY_LOG=1
+//! $ stty -F /dev/ttyUSB0 921600 raw
+//! $ cargo run --release --manifest-path log_decoder/Cargo.toml -- \
+//!       target/aarch64-unknown-none-softfloat/release/kernel < /dev/ttyUSB0
+//! ```
+//!
+//! Frames that fail to decode are reported on stderr and skipped. Decoding resumes at the next
+//! frame start.
+
+mod elf;
+mod format;
+
+#[allow(dead_code)]
+#[path = "../../src/print/binary/frame.rs"]
+mod frame;
+
+use format::{format_message, Value};
+use frame::{
+    FRAME_START, TAG_BOOL, TAG_CHAR, TAG_END, TAG_SIGNED, TAG_STR, TAG_TRUNCATED, TAG_UNSIGNED,
+};
+use std::{
+    collections::VecDeque,
+    env, fs,
+    io::{self, BufReader, Read, Write},
+    process,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+struct Decoder<'a, R> {
+    input: io::Bytes<R>,
+    /// Bytes that were read ahead, but turned out not to be part of a frame.
+    pending: VecDeque<u8>,
+    strings: &'a [u8],
+}
+
+/// Reads the fields of a frame that is already in memory.
+struct FrameReader<'a> {
+    bytes: &'a [u8],
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn level_marker(level: u8) -> Option<char> {
+    match level {
+        1 => Some('E'),
+        2 => Some('W'),
+        3 => Some(' '),
+        4 => Some('D'),
+        5 => Some('T'),
+        _ => None,
+    }
+}
+
+impl FrameReader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let (&first, rest) = self.bytes.split_first().ok_or("Frame ended unexpectedly")?;
+        self.bytes = rest;
+
+        Ok(first)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        for x in array.iter_mut() {
+            *x = self.byte()?;
+        }
+
+        Ok(array)
+    }
+
+    fn leb128(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err("LEB128 value too long".into())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self
+            .bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or("Unterminated string")?;
+        let string = String::from_utf8_lossy(&self.bytes[..len]).into_owned();
+        self.bytes = &self.bytes[(len + 1)..];
+
+        Ok(string)
+    }
+}
+
+impl<R: Read> Decoder<'_, R> {
+    fn next_byte(&mut self) -> Result<Option<u8>, String> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+
+        self.input.next().transpose().map_err(|e| e.to_string())
+    }
+
+    /// Read the length of a frame and as many of the bytes it announces as there are.
+    fn read_frame(&mut self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        let Some(len) = self.next_byte()? else {
+            return Ok(bytes);
+        };
+        bytes.push(len);
+
+        while bytes.len() <= len as usize {
+            match self.next_byte()? {
+                Some(byte) => bytes.push(byte),
+                None => break,
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decode a frame, given as its length followed by the rest of its bytes.
+    fn decode_frame(&self, bytes: &[u8]) -> Result<String, String> {
+        let (&len, body) = bytes.split_first().ok_or("Missing frame length")?;
+        if body.len() != len as usize {
+            return Err("Input ended in the middle of a frame".into());
+        }
+        if body.last() != Some(&TAG_END) {
+            return Err("Missing end tag".into());
+        }
+
+        let mut reader = FrameReader { bytes: body };
+        let id = u32::from_le_bytes(reader.array()?) as usize;
+        let timestamp = u64::from_le_bytes(reader.array()?);
+        let level = reader.byte()?;
+        let marker = level_marker(level).ok_or_else(|| format!("Unknown log level {}", level))?;
+
+        let mut args = Vec::new();
+        let mut truncated = false;
+        loop {
+            let value = match reader.byte()? {
+                TAG_END => break,
+                TAG_TRUNCATED => {
+                    truncated = true;
+                    continue;
+                }
+                TAG_UNSIGNED => Value::Unsigned(reader.leb128()?),
+                TAG_SIGNED => {
+                    let size = reader.byte()?;
+                    let x = reader.leb128()?;
+                    Value::Signed(((x >> 1) as i64) ^ -((x & 1) as i64), size)
+                }
+                TAG_CHAR => Value::Char(
+                    char::from_u32(reader.leb128()? as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+                ),
+                TAG_STR => Value::Str(reader.string()?),
+                TAG_BOOL => Value::Bool(reader.byte()? != 0),
+                x => return Err(format!("Unknown argument tag {:#x}", x)),
+            };
+            args.push(value);
+        }
+        if !reader.bytes.is_empty() {
+            return Err("Data after the end tag".into());
+        }
+
+        // Format IDs point to the start of a format string.
+        let format_string = self
+            .strings
+            .get(id..)
+            .filter(|_| id == 0 || self.strings[id - 1] == 0)
+            .and_then(|x| x.split(|&b| b == 0).next())
+            .filter(|x| !x.is_empty())
+            .map(String::from_utf8_lossy)
+            .ok_or_else(|| format!("Unknown format ID {:#x}", id))?;
+
+        Ok(format!(
+            "[{} {:>3}.{:06}] {}\n",
+            marker,
+            timestamp / 1_000_000,
+            timestamp % 1_000_000,
+            format_message(&format_string, &args, truncated)
+        ))
+    }
+
+    fn run(&mut self, output: &mut impl Write, errors: &mut impl Write) -> Result<(), String> {
+        while let Some(byte) = self.next_byte()? {
+            if byte != FRAME_START {
+                // Plain text, for example from a panic.
+                output.write_all(&[byte]).map_err(|e| e.to_string())?;
+                if byte == b'\n' {
+                    output.flush().map_err(|e| e.to_string())?;
+                }
+                continue;
+            }
+
+            let bytes = self.read_frame()?;
+            match self.decode_frame(&bytes) {
+                Ok(line) => {
+                    output
+                        .write_all(line.as_bytes())
+                        .map_err(|e| e.to_string())?;
+                    output.flush().map_err(|e| e.to_string())?;
+                }
+                Err(e) => {
+                    // The start byte might have been a stray one, so look for the next frame in
+                    // the bytes that were read after it.
+                    writeln!(errors, "log_decoder: Skipping malformed frame: {}", e)
+                        .map_err(|e| e.to_string())?;
+                    for &byte in bytes.iter().rev() {
+                        self.pending.push_front(byte);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        return Err(format!("Usage: {} <kernel ELF>", args[0]));
+    }
+
+    let elf = fs::read(&args[1]).map_err(|e| format!("{}: {}", args[1], e))?;
+    let strings = elf::find_section(&elf, ".log_strings")?;
+
+    let stdin = io::stdin();
+    let mut decoder = Decoder {
+        input: BufReader::new(stdin.lock()).bytes(),
+        pending: VecDeque::new(),
+        strings,
+    };
+
+    decoder.run(&mut io::stdout().lock(), &mut io::stderr().lock())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("log_decoder: {}", e);
+        process::exit(1);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame::Frame;
+
+    const FORMAT_STRINGS: [&str; 4] = [
+        "Booting {} on core {}",
+        "{:#06x} {:>5}|{:<4}|{:+}",
+        "{} {} {:x} {}",
+        "{1} and {0}",
+    ];
+
+    /// Build an ELF file, like the kernel's, with `.log_strings` holding `FORMAT_STRINGS`.
+    fn fixture_elf() -> Vec<u8> {
+        let strings: Vec<u8> = FORMAT_STRINGS
+            .iter()
+            .flat_map(|x| x.bytes().chain([0]))
+            .collect();
+        let names = b"\0.log_strings\0.shstrtab\0";
+
+        // ELF header, then the two sections, then the section headers.
+        let strings_offset = 0x40;
+        let names_offset = strings_offset + strings.len();
+        let shoff = (names_offset + names.len()).next_multiple_of(8);
+
+        let mut elf = vec![0; shoff];
+        elf[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        elf[0x28..0x30].copy_from_slice(&(shoff as u64).to_le_bytes());
+        elf[0x3a..0x3c].copy_from_slice(&0x40u16.to_le_bytes());
+        elf[0x3c..0x3e].copy_from_slice(&3u16.to_le_bytes());
+        elf[0x3e..0x40].copy_from_slice(&2u16.to_le_bytes());
+        elf[strings_offset..names_offset].copy_from_slice(&strings);
+        elf[names_offset..(names_offset + names.len())].copy_from_slice(names);
+
+        let sections = [
+            (0, 0, 0),
+            (1, strings_offset, strings.len()),
+            (14, names_offset, names.len()),
+        ];
+        for (name, offset, size) in sections {
+            let mut header = [0; 0x40];
+            header[..4].copy_from_slice(&(name as u32).to_le_bytes());
+            header[0x18..0x20].copy_from_slice(&(offset as u64).to_le_bytes());
+            header[0x20..0x28].copy_from_slice(&(size as u64).to_le_bytes());
+            elf.extend_from_slice(&header);
+        }
+
+        elf
+    }
+
+    /// The format ID of `FORMAT_STRINGS[index]`, which is its offset in `.log_strings`.
+    fn format_id(index: usize) -> u32 {
+        FORMAT_STRINGS[..index]
+            .iter()
+            .map(|x| x.len() + 1)
+            .sum::<usize>() as u32
+    }
+
+    /// Decode `input` and return the output and the errors.
+    fn decode(input: &[u8]) -> (String, String) {
+        let elf = fixture_elf();
+        let mut decoder = Decoder {
+            input: input.bytes(),
+            pending: VecDeque::new(),
+            strings: elf::find_section(&elf, ".log_strings").unwrap(),
+        };
+
+        let (mut output, mut errors) = (Vec::new(), Vec::new());
+        decoder.run(&mut output, &mut errors).unwrap();
+
+        (
+            String::from_utf8_lossy(&output).into_owned(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    fn booting_frame(timestamp: u64) -> Vec<u8> {
+        let mut frame = Frame::new(format_id(0), timestamp, 3);
+        frame.display("mingo");
+        frame.unsigned(0);
+
+        frame.finish().to_vec()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut input = booting_frame(1_500_042);
+
+        let mut frame = Frame::new(format_id(1), 7, 2);
+        frame.unsigned(0xab);
+        frame.char('x');
+        frame.bool(true);
+        frame.signed(42, 4);
+        input.extend_from_slice(frame.finish());
+
+        let mut frame = Frame::new(format_id(2), 8, 1);
+        frame.signed(-3, 8);
+        frame.bool(false);
+        frame.signed(-1, 2);
+        frame.display(&'\u{20ac}');
+        input.extend_from_slice(frame.finish());
+
+        let mut frame = Frame::new(format_id(3), 9, 5);
+        frame.display("first");
+        frame.display("second");
+        input.extend_from_slice(frame.finish());
+
+        let expected = [
+            "[    1.500042] Booting mingo on core 0\n".to_string(),
+            format!(
+                "[W   0.000007] {:#06x} {:>5}|{:<4}|{:+}\n",
+                0xab, 'x', true, 42
+            ),
+            format!(
+                "[E   0.000008] {} {} {:x} {}\n",
+                -3, false, -1i16, '\u{20ac}'
+            ),
+            "[T   0.000009] second and first\n".to_string(),
+        ];
+
+        assert_eq!(decode(&input), (expected.concat(), String::new()));
+    }
+
+    #[test]
+    fn text_between_frames_is_passed_through() {
+        let mut input = b"Kernel panic!\n".to_vec();
+        input.extend(booting_frame(0));
+        input.extend_from_slice("\u{20ac}\n".as_bytes());
+
+        let (output, errors) = decode(&input);
+        assert_eq!(
+            output,
+            "Kernel panic!\n[    0.000000] Booting mingo on core 0\n\u{20ac}\n"
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let long = "a".repeat(300);
+        let mut frame = Frame::new(format_id(0), 0, 3);
+        frame.display(&long);
+        frame.unsigned(1);
+        let bytes = frame.finish().to_vec();
+
+        assert!(bytes.len() <= 256);
+
+        let (output, errors) = decode(&bytes);
+        let cut = &long[..(256 - 2 - 13 - 4)];
+        assert_eq!(
+            output,
+            format!("[    0.000000] Booting {} on core <truncated>\n", cut)
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn resync_after_malformed_frames() {
+        // A frame whose end is missing, a stray start byte, and a frame with an unknown format ID.
+        let mut input = booting_frame(1)[..10].to_vec();
+        input.extend(booting_frame(2));
+        input.push(FRAME_START);
+        input.extend(b"ok\n");
+        let mut frame = Frame::new(format_id(1) + 1, 0, 3);
+        input.extend_from_slice(frame.finish());
+        input.extend(booting_frame(3));
+
+        let (output, errors) = decode(&input);
+        assert!(output.contains("[    0.000002] Booting mingo on core 0\n"));
+        assert!(output.contains("ok\n"));
+        assert!(output.ends_with("[    0.000003] Booting mingo on core 0\n"));
+        assert_eq!(errors.lines().count(), 3);
+        assert!(errors.contains("Unknown format ID"));
+    }
+
+    #[test]
+    fn frame_at_end_of_input() {
+        let (output, errors) = decode(&booting_frame(0)[..5]);
+
+        assert!(!output.contains("Booting"));
+        assert!(errors.contains("Input ended in the middle of a frame"));
+    }
+
+    #[test]
+    fn supported_format_strings() {
+        for format_string in FORMAT_STRINGS {
+            frame::check_format_string(format_string);
+        }
+        frame::check_format_string("{{:?}} {:?>5} {:\u{20ac}^3} {:.3} {0:#b}");
+    }
+
+    #[test]
+    #[should_panic(expected = "{:?} is not supported")]
+    fn debug_is_rejected() {
+        frame::check_format_string("{:?}");
+    }
+
+    #[test]
+    #[should_panic(expected = "named arguments")]
+    fn named_arguments_are_rejected() {
+        frame::check_format_string("{name}");
+    }
+
+    #[test]
+    #[should_panic(expected = "width and precision arguments")]
+    fn width_arguments_are_rejected() {
+        frame::check_format_string("{:1$}");
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported format spec")]
+    fn exponent_is_rejected() {
+        frame::check_format_string("{:e}");
+    }
+}
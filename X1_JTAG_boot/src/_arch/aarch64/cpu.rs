@@ -11,7 +11,8 @@
 //!
 //! crate::cpu::arch_cpu
 
-use aarch64_cpu::asm;
+use aarch64_cpu::{asm, registers::*};
+use tock_registers::interfaces::Readable;
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
@@ -26,3 +27,13 @@ pub fn wait_forever() -> ! {
         asm::wfe()
     }
 }
+
+/// Return whether the MMU and the data cache are enabled for the current exception level.
+pub fn is_mmu_and_dcache_enabled() -> bool {
+    match CurrentEL.read_as_enum(CurrentEL::EL) {
+        Some(CurrentEL::EL::Value::EL2) => {
+            SCTLR_EL2.matches_all(SCTLR_EL2::M::Enable + SCTLR_EL2::C::Cacheable)
+        }
+        _ => SCTLR_EL1.matches_all(SCTLR_EL1::M::Enable + SCTLR_EL1::C::Cacheable),
+    }
+}
@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural synchronous and asynchronous exception handling.
+//!
+//! The Raspberry Pi firmware starts the kernel in EL2, and the kernel stays there. Other boot
+//! stages might start it in EL1, so the EL-specific registers are chosen at runtime.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::exception::arch_exception
+
+use crate::exception;
+use aarch64_cpu::{asm::barrier, registers::*};
+use core::{arch::global_asm, cell::UnsafeCell};
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+
+// Assembly counterpart to this file.
+global_asm!(include_str!("exception.s"));
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn is_el2() -> bool {
+    CurrentEL.read_as_enum(CurrentEL::EL) == Some(CurrentEL::EL::Value::EL2)
+}
+
+/// Panic with the syndrome of an exception the kernel does not handle.
+fn default_exception_handler(kind: &str) -> ! {
+    let (esr, elr, far) = if is_el2() {
+        (ESR_EL2.get(), ELR_EL2.get(), FAR_EL2.get())
+    } else {
+        (ESR_EL1.get(), ELR_EL1.get(), FAR_EL1.get())
+    };
+
+    panic!(
+        "CPU Exception: {}\n\n      ESR: {:#010x}\n      ELR: {:#018x}\n      FAR: {:#018x}",
+        kind, esr, elr, far
+    );
+}
+
+//------------------------------------------------------------------------------
+// Current, EL0
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn current_el0_synchronous() {
+    panic!("Should not be here. Use of SP_EL0 is not supported.")
+}
+
+#[no_mangle]
+extern "C" fn current_el0_irq() {
+    panic!("Should not be here. Use of SP_EL0 is not supported.")
+}
+
+#[no_mangle]
+extern "C" fn current_el0_serror() {
+    panic!("Should not be here. Use of SP_EL0 is not supported.")
+}
+
+//------------------------------------------------------------------------------
+// Current, ELx
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn current_elx_synchronous() {
+    default_exception_handler("synchronous");
+}
+
+#[no_mangle]
+extern "C" fn current_elx_irq() {
+    exception::asynchronous::handle_pending_irqs();
+}
+
+#[no_mangle]
+extern "C" fn current_elx_serror() {
+    default_exception_handler("SError");
+}
+
+//------------------------------------------------------------------------------
+// Lower, AArch64
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn lower_aarch64_synchronous() {
+    default_exception_handler("synchronous, from lower EL");
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch64_irq() {
+    default_exception_handler("IRQ, from lower EL");
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch64_serror() {
+    default_exception_handler("SError, from lower EL");
+}
+
+//------------------------------------------------------------------------------
+// Lower, AArch32
+//------------------------------------------------------------------------------
+
+#[no_mangle]
+extern "C" fn lower_aarch32_synchronous() {
+    default_exception_handler("synchronous, from lower EL (AArch32)");
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch32_irq() {
+    default_exception_handler("IRQ, from lower EL (AArch32)");
+}
+
+#[no_mangle]
+extern "C" fn lower_aarch32_serror() {
+    default_exception_handler("SError, from lower EL (AArch32)");
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Init exception handling by setting the exception vector base address register.
+///
+/// In EL2, physical IRQs are also routed to EL2. By default, they target EL1, which means they are
+/// never taken while the core executes in EL2.
+///
+/// # Safety
+///
+/// - Changes the HW state of the executing core.
+/// - The vector table and the code it points to are part of the kernel binary, so they are always
+///   where VBAR points to.
+pub unsafe fn handling_init() {
+    // Provided by exception.s.
+    extern "Rust" {
+        static __exception_vector_start: UnsafeCell<()>;
+    }
+    let vector_start = __exception_vector_start.get() as u64;
+
+    if is_el2() {
+        VBAR_EL2.set(vector_start);
+
+        // The names of the field's values refer to virtual IRQs for EL1. Set, it also takes
+        // physical IRQs to EL2.
+        HCR_EL2.modify(HCR_EL2::IMO::EnableVirtualIRQ);
+    } else {
+        VBAR_EL1.set(vector_start);
+    }
+
+    // Force VBAR update to complete before next instruction.
+    barrier::isb(barrier::SY);
+}
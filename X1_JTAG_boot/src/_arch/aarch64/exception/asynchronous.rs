@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2018-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Architectural asynchronous exception handling.
+//!
+//! # Orientation
+//!
+//! Since arch modules are imported into generic modules using the path attribute, the path of this
+//! file is:
+//!
+//! crate::exception::asynchronous::arch_asynchronous
+
+use aarch64_cpu::registers::*;
+use core::arch::asm;
+use tock_registers::interfaces::Readable;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Immediate values for the `DAIFSet` and `DAIFClr` instructions.
+mod daif_bits {
+    pub const IRQ: u8 = 0b0010;
+    pub const FIQ: u8 = 0b0001;
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Returns whether IRQs are masked on the executing core.
+pub fn is_local_irq_masked() -> bool {
+    DAIF.is_set(DAIF::I)
+}
+
+/// Unmask IRQs on the executing core.
+///
+/// It is not needed to place an explicit instruction synchronization barrier after the `msr`.
+/// Quoting the Armv8-A Architecture Reference Manual, section C5.1.3:
+///
+/// "Writes to PSTATE.{PAN, D, A, I, F} occur in program order without the need for additional
+/// synchronization."
+#[inline(always)]
+pub fn local_irq_unmask() {
+    unsafe {
+        asm!(
+            "msr DAIFClr, {arg}",
+            arg = const daif_bits::IRQ,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+/// Mask IRQs and FIQs on the executing core.
+#[inline(always)]
+pub fn local_irq_mask() {
+    unsafe {
+        asm!(
+            "msr DAIFSet, {arg}",
+            arg = const daif_bits::IRQ | daif_bits::FIQ,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+/// Mask IRQs and FIQs on the executing core and return the previous interrupt mask bits (DAIF).
+#[inline(always)]
+pub fn local_irq_mask_save() -> u64 {
+    let saved = DAIF.get();
+
+    // Writing to DAIFSet only ever sets mask bits, so this cannot unmask anything by accident.
+    //
+    // `nomem` is deliberately not given, so that the compiler treats this as a memory barrier and
+    // does not hoist accesses of a critical section above the masking.
+    unsafe {
+        asm!(
+            "msr DAIFSet, {arg}",
+            arg = const daif_bits::IRQ | daif_bits::FIQ,
+            options(nostack, preserves_flags)
+        );
+    }
+
+    saved
+}
+
+/// Restore the interrupt mask bits (DAIF) using the callee's argument.
+///
+/// # Invariant
+///
+/// - No sanity checks on the input.
+#[inline(always)]
+pub fn local_irq_restore(saved: u64) {
+    // Like above, no `nomem`, so that accesses of a critical section are not sunk below this.
+    unsafe {
+        asm!(
+            "msr DAIF, {arg}",
+            arg = in(reg) saved,
+            options(nostack, preserves_flags)
+        );
+    }
+}
@@ -11,22 +11,16 @@
 //!
 //! crate::time::arch_time
 
-#[cfg(feature = "bsp_rpi3")]
-use crate::warn;
+use super::{counter, Error, Instant};
+use crate::synchronization::{interface::ReadWriteEx, InitStateLock};
 use aarch64_cpu::{asm::barrier, registers::*};
-use core::{
-    num::{NonZeroU128, NonZeroU32, NonZeroU64},
-    ops::{Add, Div},
-    time::Duration,
-};
-use tock_registers::interfaces::Readable;
+use core::{num::NonZeroU32, time::Duration};
+use tock_registers::interfaces::{Readable, Writeable};
 
 //--------------------------------------------------------------------------------------------------
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
-const NANOSEC_PER_SEC: NonZeroU64 = NonZeroU64::new(1_000_000_000).unwrap();
-
 #[derive(Copy, Clone, PartialOrd, PartialEq)]
 struct GenericTimerCounterValue(u64);
 
@@ -34,87 +28,35 @@ struct GenericTimerCounterValue(u64);
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
-/// Boot assembly code overwrites this value with the value of CNTFRQ_EL0 before any Rust code is
-/// executed. This given value here is just a (safe) dummy.
-#[no_mangle]
-static ARCH_TIMER_COUNTER_FREQUENCY: NonZeroU32 = NonZeroU32::MIN;
+/// The value of CNTFRQ_EL0, set once by [`init()`].
+static ARCH_TIMER_COUNTER_FREQUENCY: InitStateLock<Option<NonZeroU32>> = InitStateLock::new(None);
 
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
 fn arch_timer_counter_frequency() -> NonZeroU32 {
-    // Read volatile is needed here to prevent the compiler from optimizing
-    // ARCH_TIMER_COUNTER_FREQUENCY away.
-    //
-    // This is safe, because all the safety requirements as stated in read_volatile()'s
-    // documentation are fulfilled.
-    unsafe { core::ptr::read_volatile(&ARCH_TIMER_COUNTER_FREQUENCY) }
-}
-
-impl GenericTimerCounterValue {
-    pub const MAX: Self = GenericTimerCounterValue(u64::MAX);
-}
-
-impl Add for GenericTimerCounterValue {
-    type Output = Self;
-
-    fn add(self, other: Self) -> Self {
-        GenericTimerCounterValue(self.0.wrapping_add(other.0))
-    }
+    // Before init(), or if it failed, fall back to a (safe) dummy. Time values are meaningless
+    // then. The highest frequency converts a duration to the most counter ticks, so that delays
+    // and timeouts end late rather than early.
+    ARCH_TIMER_COUNTER_FREQUENCY
+        .read(|x| *x)
+        .unwrap_or(NonZeroU32::MAX)
 }
 
 impl From<GenericTimerCounterValue> for Duration {
     fn from(counter_value: GenericTimerCounterValue) -> Self {
-        if counter_value.0 == 0 {
-            return Duration::ZERO;
-        }
-
-        let frequency: NonZeroU64 = arch_timer_counter_frequency().into();
-
-        // Div<NonZeroU64> implementation for u64 cannot panic.
-        let secs = counter_value.0.div(frequency);
-
-        // This is safe, because frequency can never be greater than u32::MAX, which means the
-        // largest theoretical value for sub_second_counter_value is (u32::MAX - 1). Therefore,
-        // (sub_second_counter_value * NANOSEC_PER_SEC) cannot overflow an u64.
-        //
-        // The subsequent division ensures the result fits into u32, since the max result is smaller
-        // than NANOSEC_PER_SEC. Therefore, just cast it to u32 using `as`.
-        let sub_second_counter_value = counter_value.0 % frequency;
-        let nanos = unsafe { sub_second_counter_value.unchecked_mul(u64::from(NANOSEC_PER_SEC)) }
-            .div(frequency) as u32;
-
-        Duration::new(secs, nanos)
+        counter::to_duration(counter_value.0, arch_timer_counter_frequency())
     }
 }
 
-fn max_duration() -> Duration {
-    Duration::from(GenericTimerCounterValue::MAX)
-}
-
 impl TryFrom<Duration> for GenericTimerCounterValue {
     type Error = &'static str;
 
     fn try_from(duration: Duration) -> Result<Self, Self::Error> {
-        if duration < resolution() {
-            return Ok(GenericTimerCounterValue(0));
-        }
-
-        if duration > max_duration() {
-            return Err("Conversion error. Duration too big");
-        }
-
-        let frequency: u128 = u32::from(arch_timer_counter_frequency()) as u128;
-        let duration: u128 = duration.as_nanos();
-
-        // This is safe, because frequency can never be greater than u32::MAX, and
-        // (Duration::MAX.as_nanos() * u32::MAX) < u128::MAX.
-        let counter_value =
-            unsafe { duration.unchecked_mul(frequency) }.div(NonZeroU128::from(NANOSEC_PER_SEC));
-
-        // Since we checked above that we are <= max_duration(), just cast to u64.
-        Ok(GenericTimerCounterValue(counter_value as u64))
+        counter::from_duration(duration, arch_timer_counter_frequency())
+            .map(GenericTimerCounterValue)
+            .ok_or("Conversion error. Duration too big")
     }
 }
 
@@ -131,9 +73,18 @@ fn read_cntpct() -> GenericTimerCounterValue {
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
-/// The timer's resolution.
-pub fn resolution() -> Duration {
-    Duration::from(GenericTimerCounterValue(1))
+/// Read and validate the counter frequency.
+///
+/// # Safety
+///
+/// - Must only be called during kernel init.
+pub unsafe fn init() -> Result<(), Error> {
+    // The upper 32 bits are reserved, so the cast is lossless.
+    let frequency = NonZeroU32::new(CNTFRQ_EL0.get() as u32).ok_or(Error::FrequencyZero)?;
+
+    ARCH_TIMER_COUNTER_FREQUENCY.write(|x| *x = Some(frequency));
+
+    Ok(())
 }
 
 /// The uptime since power-on of the device.
@@ -143,22 +94,45 @@ pub fn uptime() -> Duration {
     read_cntpct().into()
 }
 
-/// Spin for a given duration.
-#[cfg(feature = "bsp_rpi3")]
-pub fn spin_for(duration: Duration) {
-    let curr_counter_value = read_cntpct();
-
-    let counter_value_delta: GenericTimerCounterValue = match duration.try_into() {
-        Err(msg) => {
-            warn!("spin_for: {}. Skipping", msg);
-            return;
-        }
-        Ok(val) => val,
-    };
-    let counter_value_target = curr_counter_value + counter_value_delta;
-
-    // Busy wait.
-    //
-    // Read CNTPCT_EL0 directly to avoid the ISB that is part of [`read_cntpct`].
-    while GenericTimerCounterValue(CNTPCT_EL0.get()) < counter_value_target {}
+/// Convert `duration` to counter ticks, rounding down. Returns `None` if the result does not fit.
+pub fn duration_to_ticks(duration: Duration) -> Option<u64> {
+    GenericTimerCounterValue::try_from(duration)
+        .ok()
+        .map(|x| x.0)
+}
+
+impl Instant {
+    /// Return the current instant.
+    pub fn now() -> Self {
+        Self::from_ticks(read_cntpct().0)
+    }
+
+    /// Return the time that passed since `self`.
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    /// Return the time that passed from `earlier` to `self`, or zero if `earlier` is later.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        GenericTimerCounterValue(self.ticks_since(earlier)).into()
+    }
+
+    /// Return the instant `duration` after `self`, or `None` if it is too far away to be ordered
+    /// correctly.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.checked_add_ticks(duration_to_ticks(duration)?)
+    }
+}
+
+/// Let the EL1 physical timer raise its interrupt once `deadline` has passed.
+///
+/// The interrupt stays asserted until the deadline is moved or the timer is stopped.
+pub fn set_timer_deadline(deadline: Instant) {
+    CNTP_CVAL_EL0.set(deadline.ticks());
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::CLEAR);
+}
+
+/// Stop the EL1 physical timer.
+pub fn disable_timer() {
+    CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR + CNTP_CTL_EL0::IMASK::SET);
 }
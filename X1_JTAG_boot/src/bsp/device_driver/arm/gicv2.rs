@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! GICv2 Driver - ARM Generic Interrupt Controller v2.
+//!
+//! Only the parts needed to take private peripheral interrupts, like those of the generic timer,
+//! on the boot core are supported. The firmware configures the interrupt groups and priorities.
+//!
+//! # Resources
+//!
+//! - <https://developer.arm.com/documentation/ihi0048/b/>
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    driver,
+    exception::{self, asynchronous::IRQHandlerDescriptor},
+    synchronization::{
+        interface::{Mutex, ReadWriteEx},
+        IRQSafeLock, InitStateLock,
+    },
+};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_bitfields, register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The number of interrupt numbers the handler table covers, private and shared ones.
+const NUM_IRQS: usize = 300;
+
+/// Returned by the CPU interface if no interrupt is pending.
+const SPURIOUS_IRQ: usize = 1023;
+
+register_bitfields! {
+    u32,
+
+    /// Distributor Control Register
+    GICD_CTLR [
+        Enable OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// CPU Interface Control Register
+    GICC_CTLR [
+        Enable OFFSET(0) NUMBITS(1) []
+    ],
+
+    /// Interrupt Priority Mask Register
+    GICC_PMR [
+        Priority OFFSET(0) NUMBITS(8) []
+    ],
+
+    /// Interrupt Acknowledge Register
+    GICC_IAR [
+        InterruptID OFFSET(0) NUMBITS(10) []
+    ],
+
+    /// End of Interrupt Register
+    GICC_EOIR [
+        EOIINTID OFFSET(0) NUMBITS(10) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    DistributorRegisterBlock {
+        (0x000 => CTLR: ReadWrite<u32, GICD_CTLR::Register>),
+        (0x004 => _reserved1),
+        (0x100 => ISENABLER: [ReadWrite<u32>; 32]),
+        (0x180 => ICENABLER: [ReadWrite<u32>; 32]),
+        (0x200 => @END),
+    }
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    CPUInterfaceRegisterBlock {
+        (0x000 => CTLR: ReadWrite<u32, GICC_CTLR::Register>),
+        (0x004 => PMR: ReadWrite<u32, GICC_PMR::Register>),
+        (0x008 => _reserved1),
+        (0x00C => IAR: ReadOnly<u32, GICC_IAR::Register>),
+        (0x010 => EOIR: WriteOnly<u32, GICC_EOIR::Register>),
+        (0x014 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type DistributorRegisters = MMIODerefWrapper<DistributorRegisterBlock>;
+type CPUInterfaceRegisters = MMIODerefWrapper<CPUInterfaceRegisterBlock>;
+
+type HandlerTable = [Option<IRQHandlerDescriptor<IRQNumber>>; NUM_IRQS];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// An interrupt number as used by the GIC, e.g. 16 to 31 for the private peripheral interrupts.
+#[derive(Copy, Clone)]
+pub struct IRQNumber(usize);
+
+/// Representation of the GIC.
+pub struct GICv2 {
+    gicd: IRQSafeLock<DistributorRegisters>,
+    gicc: CPUInterfaceRegisters,
+    handler_table: InitStateLock<HandlerTable>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl IRQNumber {
+    /// Create an instance.
+    pub const fn new(number: usize) -> Self {
+        assert!(number < NUM_IRQS);
+
+        Self(number)
+    }
+}
+
+impl GICv2 {
+    pub const COMPATIBLE: &'static str = "GICv2 (ARM Generic Interrupt Controller v2)";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide correct MMIO start addresses.
+    pub const unsafe fn new(gicd_mmio_start_addr: usize, gicc_mmio_start_addr: usize) -> Self {
+        Self {
+            gicd: IRQSafeLock::new(DistributorRegisters::new(gicd_mmio_start_addr)),
+            gicc: CPUInterfaceRegisters::new(gicc_mmio_start_addr),
+            handler_table: InitStateLock::new([None; NUM_IRQS]),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl driver::interface::DeviceDriver for GICv2 {
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), driver::Error> {
+        // Let interrupts of all priorities through to the core.
+        self.gicc.PMR.write(GICC_PMR::Priority.val(0xFF));
+        self.gicc.CTLR.write(GICC_CTLR::Enable::SET);
+
+        self.gicd
+            .lock(|gicd| gicd.CTLR.write(GICD_CTLR::Enable::SET));
+
+        Ok(())
+    }
+
+    unsafe fn deinit(&self) -> Result<(), driver::Error> {
+        self.gicd.lock(|gicd| {
+            for icenabler in gicd.ICENABLER.iter() {
+                icenabler.set(u32::MAX);
+            }
+
+            gicd.CTLR.write(GICD_CTLR::Enable::CLEAR);
+        });
+        self.gicc.CTLR.write(GICC_CTLR::Enable::CLEAR);
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for GICv2 {
+    type IRQNumberType = IRQNumber;
+
+    fn register_handler(
+        &self,
+        irq_handler_descriptor: IRQHandlerDescriptor<Self::IRQNumberType>,
+    ) -> Result<(), exception::asynchronous::Error> {
+        self.handler_table.write(|table| {
+            let slot = &mut table[irq_handler_descriptor.number().0];
+            if slot.is_some() {
+                return Err(exception::asynchronous::Error::AlreadyRegistered);
+            }
+
+            *slot = Some(irq_handler_descriptor);
+            Ok(())
+        })
+    }
+
+    fn enable(&self, irq: &Self::IRQNumberType) {
+        // Writing zeroes has no effect, so no read-modify-write is needed.
+        self.gicd
+            .lock(|gicd| gicd.ISENABLER[irq.0 / 32].set(1 << (irq.0 % 32)));
+    }
+
+    fn handle_pending_irqs(&self) {
+        // Acknowledging marks the interrupt active, so it is not signaled again until its end.
+        let iar = self.gicc.IAR.get();
+        let number = (iar & 0x3FF) as usize;
+
+        if number == SPURIOUS_IRQ {
+            return;
+        }
+
+        self.handler_table
+            .read(|table| match table.get(number).copied().flatten() {
+                Some(descriptor) => descriptor.handler().handle(),
+                None => panic!("No handler registered for IRQ {}", number),
+            });
+
+        // The end of interrupt register takes the acknowledged value as is, including the ID of
+        // the core that raised a software generated interrupt.
+        self.gicc.EOIR.set(iar);
+    }
+}
@@ -4,8 +4,12 @@
 
 //! BCM driver top level.
 
+#[cfg(feature = "bsp_rpi3")]
+mod bcm2836_local_ic;
 mod bcm2xxx_gpio;
 mod bcm2xxx_pl011_uart;
 
+#[cfg(feature = "bsp_rpi3")]
+pub use bcm2836_local_ic::*;
 pub use bcm2xxx_gpio::*;
 pub use bcm2xxx_pl011_uart::*;
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Local Interrupt Controller Driver.
+//!
+//! The ARM local peripherals of the BCM2836, which the BCM2837 has as well, route the interrupts of
+//! each core's generic timers. Only the routing to core 0, the boot core, is supported.
+//!
+//! # Resources
+//!
+//! - <https://datasheets.raspberrypi.com/bcm2836/bcm2836-peripherals.pdf>
+
+use crate::{
+    bsp::device_driver::common::MMIODerefWrapper,
+    driver,
+    exception::{self, asynchronous::IRQHandlerDescriptor},
+    synchronization::{
+        interface::{Mutex, ReadWriteEx},
+        IRQSafeLock, InitStateLock,
+    },
+};
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The number of interrupt sources of the core timers.
+const NUM_TIMER_IRQS: usize = 4;
+
+register_structs! {
+    #[allow(non_snake_case)]
+    RegisterBlock {
+        (0x00 => _reserved1),
+        /// Bits 0 to 3 route CNTPSIRQ, CNTPNSIRQ, CNTHPIRQ and CNTVIRQ to the core's IRQ.
+        (0x40 => CORE0_TIMER_IRQCNTL: ReadWrite<u32>),
+        (0x44 => _reserved2),
+        /// Pending interrupt sources. Bits 0 to 3 are the timers, in the same order as above.
+        (0x60 => CORE0_IRQ_SOURCE: ReadOnly<u32>),
+        (0x64 => @END),
+    }
+}
+
+/// Abstraction for the associated MMIO registers.
+type Registers = MMIODerefWrapper<RegisterBlock>;
+
+type HandlerTable = [Option<IRQHandlerDescriptor<LocalIRQ>>; NUM_TIMER_IRQS];
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A local interrupt source, which is the bit number in the core's IRQ source register.
+#[derive(Copy, Clone)]
+pub struct LocalIRQ(usize);
+
+/// Representation of the local interrupt controller.
+pub struct LocalIC {
+    registers: IRQSafeLock<Registers>,
+    handler_table: InitStateLock<HandlerTable>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl LocalIRQ {
+    /// Create an instance.
+    ///
+    /// Only the interrupts of the core timers, 0 to 3, are supported.
+    pub const fn new(number: usize) -> Self {
+        assert!(number < NUM_TIMER_IRQS);
+
+        Self(number)
+    }
+}
+
+impl LocalIC {
+    pub const COMPATIBLE: &'static str = "BCM Local Interrupt Controller";
+
+    /// Create an instance.
+    ///
+    /// # Safety
+    ///
+    /// - The user must ensure to provide a correct MMIO start address.
+    pub const unsafe fn new(mmio_start_addr: usize) -> Self {
+        Self {
+            registers: IRQSafeLock::new(Registers::new(mmio_start_addr)),
+            handler_table: InitStateLock::new([None; NUM_TIMER_IRQS]),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl driver::interface::DeviceDriver for LocalIC {
+    fn compatible(&self) -> &'static str {
+        Self::COMPATIBLE
+    }
+
+    unsafe fn init(&self) -> Result<(), driver::Error> {
+        // Start with all timer interrupts disabled. They are enabled as handlers are registered.
+        self.registers.lock(|regs| regs.CORE0_TIMER_IRQCNTL.set(0));
+
+        Ok(())
+    }
+
+    unsafe fn deinit(&self) -> Result<(), driver::Error> {
+        self.registers.lock(|regs| regs.CORE0_TIMER_IRQCNTL.set(0));
+
+        Ok(())
+    }
+}
+
+impl exception::asynchronous::interface::IRQManager for LocalIC {
+    type IRQNumberType = LocalIRQ;
+
+    fn register_handler(
+        &self,
+        irq_handler_descriptor: IRQHandlerDescriptor<Self::IRQNumberType>,
+    ) -> Result<(), exception::asynchronous::Error> {
+        self.handler_table.write(|table| {
+            let slot = &mut table[irq_handler_descriptor.number().0];
+            if slot.is_some() {
+                return Err(exception::asynchronous::Error::AlreadyRegistered);
+            }
+
+            *slot = Some(irq_handler_descriptor);
+            Ok(())
+        })
+    }
+
+    fn enable(&self, irq: &Self::IRQNumberType) {
+        self.registers.lock(|regs| {
+            let enabled = regs.CORE0_TIMER_IRQCNTL.get();
+            regs.CORE0_TIMER_IRQCNTL.set(enabled | (1 << irq.0));
+        });
+    }
+
+    fn handle_pending_irqs(&self) {
+        let pending = self.registers.lock(|regs| regs.CORE0_IRQ_SOURCE.get());
+
+        self.handler_table.read(|table| {
+            for (number, slot) in table.iter().enumerate() {
+                if pending & (1 << number) == 0 {
+                    continue;
+                }
+
+                match slot {
+                    Some(descriptor) => descriptor.handler().handle(),
+                    None => panic!("No handler registered for local IRQ {}", number),
+                }
+            }
+        })
+    }
+}
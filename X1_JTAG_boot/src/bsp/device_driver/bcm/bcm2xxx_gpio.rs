@@ -5,11 +5,12 @@
 //! GPIO Driver.
 
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, driver, synchronization,
-    synchronization::NullLock,
+    bsp::device_driver::common::MMIODerefWrapper, debug, driver, synchronization,
+    synchronization::SpinLock, time,
 };
+use core::time::Duration;
 use tock_registers::{
-    interfaces::{ReadWriteable, Writeable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields, register_structs,
     registers::ReadWrite,
 };
@@ -18,6 +19,11 @@ use tock_registers::{
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
+/// How long changes to the pull-up/down configuration take to settle.
+///
+/// The Linux 2837 GPIO driver waits 1 µs between the steps of its sequence.
+const PUD_SETTLE_DELAY: Duration = Duration::from_micros(1);
+
 // GPIO registers.
 //
 // Descriptions taken from
@@ -110,6 +116,9 @@ type Registers = MMIODerefWrapper<RegisterBlock>;
 
 struct GPIOInner {
     registers: Registers,
+
+    /// Function selects of pins 14 and 15 from before the PL011 UART was mapped.
+    saved_fsel_14_15: Option<(u32, u32)>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -118,7 +127,7 @@ struct GPIOInner {
 
 /// Representation of the GPIO HW.
 pub struct GPIO {
-    inner: NullLock<GPIOInner>,
+    inner: SpinLock<GPIOInner>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -134,25 +143,20 @@ impl GPIOInner {
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            saved_fsel_14_15: None,
         }
     }
 
     /// Disable pull-up/down on pins 14 and 15.
     #[cfg(feature = "bsp_rpi3")]
     fn disable_pud_14_15_bcm2837(&mut self) {
-        use crate::time;
-        use core::time::Duration;
-
-        // The Linux 2837 GPIO driver waits 1 µs between the steps.
-        const DELAY: Duration = Duration::from_micros(1);
-
         self.registers.GPPUD.write(GPPUD::PUD::Off);
-        time::time_manager().spin_for(DELAY);
+        time::time_manager().spin_for(PUD_SETTLE_DELAY);
 
         self.registers
             .GPPUDCLK0
             .write(GPPUDCLK0::PUDCLK15::AssertClock + GPPUDCLK0::PUDCLK14::AssertClock);
-        time::time_manager().spin_for(DELAY);
+        time::time_manager().spin_for(PUD_SETTLE_DELAY);
 
         self.registers.GPPUD.write(GPPUD::PUD::Off);
         self.registers.GPPUDCLK0.set(0);
@@ -165,6 +169,9 @@ impl GPIOInner {
             GPIO_PUP_PDN_CNTRL_REG0::GPIO_PUP_PDN_CNTRL15::PullUp
                 + GPIO_PUP_PDN_CNTRL_REG0::GPIO_PUP_PDN_CNTRL14::PullUp,
         );
+
+        // Let the pins reach their new level before they are handed to the UART.
+        time::time_manager().spin_for(PUD_SETTLE_DELAY);
     }
 
     /// Map PL011 UART as standard output.
@@ -172,6 +179,14 @@ impl GPIOInner {
     /// TX to pin 14
     /// RX to pin 15
     pub fn map_pl011_uart(&mut self) {
+        // Remember the previous function selects, so that deinit() can restore them.
+        if self.saved_fsel_14_15.is_none() {
+            self.saved_fsel_14_15 = Some((
+                self.registers.GPFSEL1.read(GPFSEL1::FSEL14),
+                self.registers.GPFSEL1.read(GPFSEL1::FSEL15),
+            ));
+        }
+
         // Select the UART on pins 14 and 15.
         self.registers
             .GPFSEL1
@@ -184,6 +199,15 @@ impl GPIOInner {
         #[cfg(feature = "bsp_rpi4")]
         self.disable_pud_14_15_bcm2711();
     }
+
+    /// Restore the function selects of pins 14 and 15 to what they were before mapping the UART.
+    pub fn deinit(&mut self) {
+        if let Some((fsel14, fsel15)) = self.saved_fsel_14_15.take() {
+            self.registers
+                .GPFSEL1
+                .modify(GPFSEL1::FSEL14.val(fsel14) + GPFSEL1::FSEL15.val(fsel15));
+        }
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -200,13 +224,15 @@ impl GPIO {
     /// - The user must ensure to provide a correct MMIO start address.
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
-            inner: NullLock::new(GPIOInner::new(mmio_start_addr)),
+            inner: SpinLock::new(GPIOInner::new(mmio_start_addr)),
         }
     }
 
     /// Concurrency safe version of `GPIOInner.map_pl011_uart()`
     pub fn map_pl011_uart(&self) {
-        self.inner.lock(|inner| inner.map_pl011_uart())
+        self.inner.lock(|inner| inner.map_pl011_uart());
+
+        debug!("Mapped PL011 UART to GPIO 14 and 15");
     }
 }
 
@@ -219,4 +245,10 @@ impl driver::interface::DeviceDriver for GPIO {
     fn compatible(&self) -> &'static str {
         Self::COMPATIBLE
     }
+
+    unsafe fn deinit(&self) -> Result<(), driver::Error> {
+        self.inner.lock(|inner| inner.deinit());
+
+        Ok(())
+    }
 }
@@ -10,10 +10,14 @@
 //! - <https://developer.arm.com/documentation/ddi0183/latest>
 
 use crate::{
-    bsp::device_driver::common::MMIODerefWrapper, console, cpu, driver, synchronization,
-    synchronization::NullLock,
+    bsp::device_driver::common::MMIODerefWrapper,
+    console,
+    console::{LineDiscipline, LineEnding},
+    cpu, debug, driver, synchronization,
+    synchronization::IRQSafeLock,
+    time,
 };
-use core::fmt;
+use core::{fmt, time::Duration};
 use tock_registers::{
     interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
@@ -30,6 +34,34 @@ use tock_registers::{
 register_bitfields! {
     u32,
 
+    /// Data Register.
+    DR [
+        /// Overrun error. Set if data is received and the receive FIFO is already full. The FIFO
+        /// contents remain valid because no more data is written when the FIFO is full, only the
+        /// contents of the shift register are overwritten.
+        OE OFFSET(11) NUMBITS(1) [],
+
+        /// Break error. Set if a break condition was detected, indicating that the received data
+        /// input was held LOW for longer than a full-word transmission time.
+        BE OFFSET(10) NUMBITS(1) [],
+
+        /// Parity error. Set if the parity of the received data character does not match the
+        /// parity that the EPS and SPS bits in the Line Control Register, LCR_H, select.
+        PE OFFSET(9) NUMBITS(1) [],
+
+        /// Framing error. Set if the received character did not have a valid stop bit.
+        FE OFFSET(8) NUMBITS(1) [],
+
+        /// Receive (read) data character. Transmit (write) data character.
+        DATA OFFSET(0) NUMBITS(8) []
+    ],
+
+    /// Receive Status Register / Error Clear Register.
+    RSR_ECR [
+        /// Meta field for all error flags. A write to the ECR clears them.
+        ALL OFFSET(0) NUMBITS(4) []
+    ],
+
     /// Flag Register.
     FR [
         /// Transmit FIFO empty. The meaning of this bit depends on the state of the FEN bit in the
@@ -144,8 +176,9 @@ register_bitfields! {
 register_structs! {
     #[allow(non_snake_case)]
     pub RegisterBlock {
-        (0x00 => DR: ReadWrite<u32>),
-        (0x04 => _reserved1),
+        (0x00 => DR: ReadWrite<u32, DR::Register>),
+        (0x04 => RSR_ECR: ReadWrite<u32, RSR_ECR::Register>),
+        (0x08 => _reserved1),
         (0x18 => FR: ReadOnly<u32, FR::Register>),
         (0x1c => _reserved2),
         (0x24 => IBRD: WriteOnly<u32, IBRD::Register>),
@@ -161,7 +194,11 @@ register_structs! {
 /// Abstraction for the associated MMIO registers.
 type Registers = MMIODerefWrapper<RegisterBlock>;
 
-#[derive(PartialEq)]
+/// How long `flush()` waits at most. Draining the 32 byte deep TX FIFO takes less than 3 ms, even
+/// at the 115_200 baud the firmware might have left configured before `init()`.
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(10);
+
+#[derive(Copy, Clone, PartialEq)]
 enum BlockingMode {
     Blocking,
     NonBlocking,
@@ -169,8 +206,15 @@ enum BlockingMode {
 
 struct PL011UartInner {
     registers: Registers,
+    enabled: bool,
+    line_discipline: LineDiscipline,
     chars_written: usize,
     chars_read: usize,
+    rx_overrun_errors: usize,
+    rx_framing_errors: usize,
+    rx_parity_errors: usize,
+    rx_break_errors: usize,
+    tx_peak_stall_time: Duration,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -179,7 +223,7 @@ struct PL011UartInner {
 
 /// Representation of the UART.
 pub struct PL011Uart {
-    inner: NullLock<PL011UartInner>,
+    inner: IRQSafeLock<PL011UartInner>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -195,8 +239,15 @@ impl PL011UartInner {
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
             registers: Registers::new(mmio_start_addr),
+            enabled: false,
+            line_discipline: LineDiscipline::new(LineEnding::CrLf),
             chars_written: 0,
             chars_read: 0,
+            rx_overrun_errors: 0,
+            rx_framing_errors: 0,
+            rx_parity_errors: 0,
+            rx_break_errors: 0,
+            tx_peak_stall_time: Duration::ZERO,
         }
     }
 
@@ -217,7 +268,7 @@ impl PL011UartInner {
     /// genrated baud rate of `48_000_000 / (16 * 3.25) = 923_077`.
     ///
     /// Error = `((923_077 - 921_600) / 921_600) * 100 = 0.16%`.
-    pub fn init(&mut self) {
+    pub fn init(&mut self) -> Result<(), driver::Error> {
         // Execution can arrive here while there are still characters queued in the TX FIFO and
         // actively being sent out by the UART hardware. If the UART is turned off in this case,
         // those queued characters would be lost.
@@ -226,13 +277,15 @@ impl PL011UartInner {
         // initializes its own UART instance and calls init().
         //
         // Hence, flush first to ensure all pending characters are transmitted.
-        self.flush();
+        self.flush()
+            .map_err(|_| driver::Error::HardwareNotResponding)?;
 
         // Turn the UART off temporarily.
         self.registers.CR.set(0);
 
-        // Clear all pending interrupts.
+        // Clear all pending interrupts and receive errors.
         self.registers.ICR.write(ICR::ALL::CLEAR);
+        self.registers.RSR_ECR.write(RSR_ECR::ALL::CLEAR);
 
         // From the PL011 Technical Reference Manual:
         //
@@ -251,31 +304,81 @@ impl PL011UartInner {
         self.registers
             .CR
             .write(CR::UARTEN::Enabled + CR::TXE::Enabled + CR::RXE::Enabled);
+        self.enabled = true;
+
+        Ok(())
+    }
+
+    /// Drain the TX FIFO and turn the UART off.
+    ///
+    /// Characters written afterwards are dropped instead of filling up the FIFO of the disabled
+    /// UART, which would make writers spin forever.
+    pub fn deinit(&mut self) -> Result<(), driver::Error> {
+        let result = self
+            .flush()
+            .map_err(|_| driver::Error::HardwareNotResponding);
+
+        self.registers.CR.set(0);
+        self.registers.ICR.write(ICR::ALL::CLEAR);
+        self.enabled = false;
+
+        result
+    }
+
+    /// Send a byte.
+    fn write_byte(&mut self, byte: u8) {
+        // Spin while TX FIFO full is set, waiting for an empty slot. Only take timestamps if there
+        // is a stall, to keep the common path fast.
+        if self.registers.FR.matches_all(FR::TXFF::SET) {
+            let start = time::Instant::now();
+
+            while self.registers.FR.matches_all(FR::TXFF::SET) {
+                cpu::nop();
+            }
+
+            let stall_time = start.elapsed();
+            self.tx_peak_stall_time = self.tx_peak_stall_time.max(stall_time);
+        }
+
+        // Write the byte to the buffer.
+        self.registers.DR.set(byte as u32);
     }
 
-    /// Send a character.
+    /// Send raw bytes.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        bytes.iter().for_each(|&b| self.write_byte(b));
+        self.chars_written += bytes.len();
+    }
+
+    /// Send a character through the line discipline.
     fn write_char(&mut self, c: char) {
-        // Spin while TX FIFO full is set, waiting for an empty slot.
-        while self.registers.FR.matches_all(FR::TXFF::SET) {
-            cpu::nop();
+        if !self.enabled {
+            return;
         }
 
-        // Write the character to the buffer.
-        self.registers.DR.set(c as u32);
+        let line_ending = self.line_discipline.line_ending();
+        line_ending.encode(c, |b| self.write_byte(b));
 
         self.chars_written += 1;
     }
 
     /// Block execution until the last buffered character has been physically put on the TX wire.
-    fn flush(&self) {
+    ///
+    /// Gives up if the UART stays busy for much longer than draining a full TX FIFO takes.
+    fn flush(&self) -> Result<(), time::Error> {
         // Spin until the busy bit is cleared.
-        while self.registers.FR.matches_all(FR::BUSY::SET) {
-            cpu::nop();
-        }
+        time::time_manager().spin_while(
+            || self.registers.FR.matches_all(FR::BUSY::SET),
+            FLUSH_TIMEOUT,
+        )
     }
 
-    /// Retrieve a character.
-    fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
+    /// Retrieve a raw byte.
+    fn read_byte(&mut self, blocking_mode: BlockingMode) -> Option<u8> {
         // If RX FIFO is empty,
         if self.registers.FR.matches_all(FR::RXFE::SET) {
             // immediately return in non-blocking mode.
@@ -283,24 +386,45 @@ impl PL011UartInner {
                 return None;
             }
 
-            // Otherwise, wait until a char was received.
+            // Otherwise, wait until a byte was received.
             while self.registers.FR.matches_all(FR::RXFE::SET) {
                 cpu::nop();
             }
         }
 
-        // Read one character.
-        let mut ret = self.registers.DR.get() as u8 as char;
+        // Read one byte, together with its error flags.
+        let dr = self.registers.DR.extract();
 
-        // Convert carrige return to newline.
-        if ret == '\r' {
-            ret = '\n'
+        // Update statistics.
+        if dr.is_set(DR::OE) {
+            self.rx_overrun_errors += 1;
+        }
+        if dr.is_set(DR::FE) {
+            self.rx_framing_errors += 1;
+        }
+        if dr.is_set(DR::PE) {
+            self.rx_parity_errors += 1;
+        }
+        if dr.is_set(DR::BE) {
+            self.rx_break_errors += 1;
         }
 
+        Some(dr.read(DR::DATA) as u8)
+    }
+
+    /// Retrieve a character through the line discipline.
+    fn read_char_converting(&mut self, blocking_mode: BlockingMode) -> Option<char> {
+        // The line discipline pulls the bytes from the FIFO, so it must be taken out of `self`.
+        let mut line_discipline = self.line_discipline;
+        let ret = line_discipline.decode(|| self.read_byte(blocking_mode));
+        self.line_discipline = line_discipline;
+
         // Update statistics.
-        self.chars_read += 1;
+        if ret.is_some() {
+            self.chars_read += 1;
+        }
 
-        Some(ret)
+        ret
     }
 }
 
@@ -337,7 +461,7 @@ impl PL011Uart {
     /// - The user must ensure to provide a correct MMIO start address.
     pub const unsafe fn new(mmio_start_addr: usize) -> Self {
         Self {
-            inner: NullLock::new(PL011UartInner::new(mmio_start_addr)),
+            inner: IRQSafeLock::new(PL011UartInner::new(mmio_start_addr)),
         }
     }
 }
@@ -352,11 +476,16 @@ impl driver::interface::DeviceDriver for PL011Uart {
         Self::COMPATIBLE
     }
 
-    unsafe fn init(&self) -> Result<(), &'static str> {
-        self.inner.lock(|inner| inner.init());
+    unsafe fn init(&self) -> Result<(), driver::Error> {
+        self.inner.lock(|inner| inner.init())?;
+        debug!("PL011 UART initialized");
 
         Ok(())
     }
+
+    unsafe fn deinit(&self) -> Result<(), driver::Error> {
+        self.inner.lock(|inner| inner.deinit())
+    }
 }
 
 impl console::interface::Write for PL011Uart {
@@ -372,9 +501,18 @@ impl console::interface::Write for PL011Uart {
         self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
     }
 
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.inner.lock(|inner| inner.write_bytes(bytes));
+    }
+
+    fn set_line_ending(&self, line_ending: console::LineEnding) {
+        self.inner
+            .lock(|inner| inner.line_discipline.set_line_ending(line_ending));
+    }
+
     fn flush(&self) {
-        // Spin until TX FIFO empty is set.
-        self.inner.lock(|inner| inner.flush());
+        // Spin until the UART is no longer busy. There is nobody to report a timeout to.
+        let _ = self.inner.lock(|inner| inner.flush());
     }
 }
 
@@ -384,13 +522,23 @@ impl console::interface::Read for PL011Uart {
             .lock(|inner| inner.read_char_converting(BlockingMode::Blocking).unwrap())
     }
 
+    fn try_read_char(&self) -> Option<char> {
+        self.inner
+            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
+    }
+
+    fn read_byte(&self) -> u8 {
+        self.inner.lock(|inner| {
+            let byte = inner.read_byte(BlockingMode::Blocking).unwrap();
+            inner.chars_read += 1;
+
+            byte
+        })
+    }
+
     fn clear_rx(&self) {
         // Read from the RX FIFO until it is indicating empty.
-        while self
-            .inner
-            .lock(|inner| inner.read_char_converting(BlockingMode::NonBlocking))
-            .is_some()
-        {}
+        while self.try_read_char().is_some() {}
     }
 }
 
@@ -402,6 +550,26 @@ impl console::interface::Statistics for PL011Uart {
     fn chars_read(&self) -> usize {
         self.inner.lock(|inner| inner.chars_read)
     }
+
+    fn rx_overrun_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.rx_overrun_errors)
+    }
+
+    fn rx_framing_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.rx_framing_errors)
+    }
+
+    fn rx_parity_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.rx_parity_errors)
+    }
+
+    fn rx_break_errors(&self) -> usize {
+        self.inner.lock(|inner| inner.rx_break_errors)
+    }
+
+    fn tx_peak_stall_time(&self) -> Duration {
+        self.inner.lock(|inner| inner.tx_peak_stall_time)
+    }
 }
 
 impl console::interface::All for PL011Uart {}
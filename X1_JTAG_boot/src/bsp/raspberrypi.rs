@@ -6,4 +6,5 @@
 
 pub mod cpu;
 pub mod driver;
+pub mod exception;
 pub mod memory;
@@ -5,8 +5,14 @@
 //! BSP driver support.
 
 use super::memory::map::mmio;
-use crate::{bsp::device_driver, console, driver as generic_driver};
-use core::sync::atomic::{AtomicBool, Ordering};
+use crate::{bsp::device_driver, console, driver as generic_driver, exception, state, time, trace};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The maximum number of drivers the BSP can register with the driver manager.
+pub const NUM_DRIVERS: usize = 5;
 
 //--------------------------------------------------------------------------------------------------
 // Global instances
@@ -16,36 +22,61 @@ static PL011_UART: device_driver::PL011Uart =
     unsafe { device_driver::PL011Uart::new(mmio::PL011_UART_START) };
 static GPIO: device_driver::GPIO = unsafe { device_driver::GPIO::new(mmio::GPIO_START) };
 
+#[cfg(feature = "bsp_rpi3")]
+static INTERRUPT_CONTROLLER: device_driver::LocalIC =
+    unsafe { device_driver::LocalIC::new(mmio::LOCAL_IC_START) };
+
+#[cfg(feature = "bsp_rpi4")]
+static INTERRUPT_CONTROLLER: device_driver::GICv2 =
+    unsafe { device_driver::GICv2::new(mmio::GICD_START, mmio::GICC_START) };
+
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
-/// This must be called only after successful init of the UART driver.
-fn post_init_uart() -> Result<(), &'static str> {
-    console::register_console(&PL011_UART);
+/// This must be called only after successful init of the GPIO driver, which in turn depends on the
+/// UART.
+fn post_init_gpio() -> Result<(), generic_driver::Error> {
+    GPIO.map_pl011_uart();
 
-    Ok(())
+    // Registering replays the log buffer, so the UART must only become a console once its pins are
+    // routed.
+    console::register_console(&PL011_UART)
+        .map_err(|_| generic_driver::Error::Custom("Registering as console failed"))
 }
 
-/// This must be called only after successful init of the GPIO driver.
-fn post_init_gpio() -> Result<(), &'static str> {
-    GPIO.map_pl011_uart();
+/// This must be called only after successful init of the interrupt controller driver.
+fn post_init_interrupt_controller() -> Result<(), generic_driver::Error> {
+    exception::asynchronous::register_irq_manager(&INTERRUPT_CONTROLLER);
+
     Ok(())
 }
 
-fn driver_uart() -> Result<(), &'static str> {
-    let uart_descriptor =
-        generic_driver::DeviceDriverDescriptor::new(&PL011_UART, Some(post_init_uart));
-    generic_driver::driver_manager().register_driver(uart_descriptor);
-
-    Ok(())
+fn driver_uart() -> Result<(), generic_driver::Error> {
+    let uart_descriptor = generic_driver::DeviceDriverDescriptor::new(&PL011_UART, None, true, &[]);
+    generic_driver::driver_manager().register_driver(uart_descriptor)
 }
 
-fn driver_gpio() -> Result<(), &'static str> {
-    let gpio_descriptor = generic_driver::DeviceDriverDescriptor::new(&GPIO, Some(post_init_gpio));
-    generic_driver::driver_manager().register_driver(gpio_descriptor);
+fn driver_gpio() -> Result<(), generic_driver::Error> {
+    // Routing the pins must only happen once the UART is configured and idle, so that no garbage is
+    // put on the wire.
+    let gpio_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        &GPIO,
+        Some(post_init_gpio),
+        true,
+        &[device_driver::PL011Uart::COMPATIBLE],
+    );
+    generic_driver::driver_manager().register_driver(gpio_descriptor)
+}
 
-    Ok(())
+fn driver_interrupt_controller() -> Result<(), generic_driver::Error> {
+    let interrupt_controller_descriptor = generic_driver::DeviceDriverDescriptor::new(
+        &INTERRUPT_CONTROLLER,
+        Some(post_init_interrupt_controller),
+        true,
+        &[],
+    );
+    generic_driver::driver_manager().register_driver(interrupt_controller_descriptor)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -54,18 +85,52 @@ fn driver_gpio() -> Result<(), &'static str> {
 
 /// Initialize the driver subsystem.
 ///
+/// Only possible once, while the kernel is in its init state.
+///
 /// # Safety
 ///
 /// See child function calls.
-pub unsafe fn init() -> Result<(), &'static str> {
-    static INIT_DONE: AtomicBool = AtomicBool::new(false);
-    if INIT_DONE.load(Ordering::Relaxed) {
-        return Err("Init already done");
+pub unsafe fn init() -> Result<(), generic_driver::Error> {
+    if !state::state_manager().is_init() {
+        return Err(generic_driver::Error::AlreadyInitialized);
     }
 
-    driver_uart()?;
-    driver_gpio()?;
+    // The BSP's drivers are only registered here, so finding one of them registered already means
+    // that this function ran before.
+    let already_initialized = |e| match e {
+        generic_driver::Error::AlreadyRegistered => generic_driver::Error::AlreadyInitialized,
+        e => e,
+    };
+
+    driver_uart().map_err(already_initialized)?;
+    driver_gpio().map_err(already_initialized)?;
+    driver_interrupt_controller().map_err(already_initialized)?;
 
-    INIT_DONE.store(true, Ordering::Relaxed);
     Ok(())
 }
+
+/// Shut down the driver subsystem.
+///
+/// # Safety
+///
+/// See child function calls.
+pub unsafe fn shutdown() -> Result<(), generic_driver::DriverError> {
+    use console::interface::Write;
+
+    // Last chance to print to the UART.
+    trace!("Shutting down drivers");
+
+    // Stop printing to the UART before turning it off. It is not registered if it failed to come
+    // up, so the error can be ignored.
+    let _ = console::remove_console(&PL011_UART);
+
+    // The GPIO depends on the UART, so it is shut down first. Drain the TX FIFO while the pins are
+    // still routed to the UART, or the end of the last message is cut off.
+    PL011_UART.flush();
+
+    // The timer must not keep running into whatever gets loaded over JTAG next. Its interrupt is
+    // masked by now, and the interrupt controller's shutdown stops routing it.
+    time::time_manager().cancel_timeouts();
+
+    generic_driver::driver_manager().shutdown_drivers()
+}
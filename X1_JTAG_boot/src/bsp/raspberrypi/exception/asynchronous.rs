@@ -0,0 +1,34 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! BSP asynchronous exception handling.
+
+use crate::bsp::device_driver;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The interrupt number type of the board's interrupt controller.
+#[cfg(feature = "bsp_rpi3")]
+pub type IRQNumber = device_driver::LocalIRQ;
+
+/// The interrupt number type of the board's interrupt controller.
+#[cfg(feature = "bsp_rpi4")]
+pub type IRQNumber = device_driver::IRQNumber;
+
+/// The board's interrupts.
+pub mod irq_map {
+    use super::IRQNumber;
+
+    /// The non-secure physical timer of the boot core, CNTPNSIRQ.
+    #[cfg(feature = "bsp_rpi3")]
+    pub const ARM_NS_PHYSICAL_TIMER: IRQNumber = IRQNumber::new(1);
+
+    /// The non-secure physical timer of the boot core, CNTPNSIRQ.
+    ///
+    /// Private peripheral interrupt 14.
+    #[cfg(feature = "bsp_rpi4")]
+    pub const ARM_NS_PHYSICAL_TIMER: IRQNumber = IRQNumber::new(30);
+}
@@ -23,6 +23,7 @@ pub(super) mod map {
         pub const START:            usize =         0x3F00_0000;
         pub const GPIO_START:       usize = START + GPIO_OFFSET;
         pub const PL011_UART_START: usize = START + UART_OFFSET;
+        pub const LOCAL_IC_START:   usize =         0x4000_0000;
     }
 
     /// Physical devices.
@@ -33,5 +34,7 @@ pub(super) mod map {
         pub const START:            usize =         0xFE00_0000;
         pub const GPIO_START:       usize = START + GPIO_OFFSET;
         pub const PL011_UART_START: usize = START + UART_OFFSET;
+        pub const GICD_START:       usize =         0xFF84_1000;
+        pub const GICC_START:       usize =         0xFF84_2000;
     }
 }
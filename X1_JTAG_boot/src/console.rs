@@ -4,9 +4,17 @@
 
 //! System console.
 
-mod null_console;
+pub mod ansi;
 
-use crate::synchronization::{self, NullLock};
+mod line_discipline;
+mod line_edit;
+mod log_buffer;
+mod mux_console;
+
+use crate::{cpu, info};
+use core::fmt;
+
+pub use line_discipline::{LineDiscipline, LineEnding};
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
@@ -14,7 +22,9 @@ use crate::synchronization::{self, NullLock};
 
 /// Console interfaces.
 pub mod interface {
-    use core::fmt;
+    use super::LineEnding;
+    use crate::{cpu, time};
+    use core::{fmt, time::Duration};
 
     /// Console write functions.
     pub trait Write {
@@ -24,6 +34,12 @@ pub mod interface {
         /// Write a Rust format string.
         fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result;
 
+        /// Write raw bytes, without any conversion.
+        fn write_bytes(&self, bytes: &[u8]);
+
+        /// Select the line ending translation of the text path.
+        fn set_line_ending(&self, _line_ending: LineEnding) {}
+
         /// Block until the last buffered character has been physically put on the TX wire.
         fn flush(&self);
     }
@@ -35,6 +51,34 @@ pub mod interface {
             ' '
         }
 
+        /// Read a single raw byte, without any conversion.
+        fn read_byte(&self) -> u8 {
+            0
+        }
+
+        /// Read a single character if one is available, without blocking.
+        fn try_read_char(&self) -> Option<char> {
+            None
+        }
+
+        /// Read a single character, giving up after `timeout`.
+        fn read_char_timeout(&self, timeout: Duration) -> Option<char> {
+            // A timeout too big to be represented means no timeout at all.
+            let deadline = time::Instant::now().checked_add(timeout);
+
+            loop {
+                if let Some(c) = self.try_read_char() {
+                    return Some(c);
+                }
+
+                if deadline.is_some_and(|x| time::Instant::now() >= x) {
+                    return None;
+                }
+
+                cpu::nop();
+            }
+        }
+
         /// Clear RX buffers, if any.
         fn clear_rx(&self);
     }
@@ -50,32 +94,159 @@ pub mod interface {
         fn chars_read(&self) -> usize {
             0
         }
+
+        /// Return the number of received characters that were followed by lost input, because
+        /// the RX buffer was full.
+        fn rx_overrun_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the number of received characters without a valid stop bit.
+        fn rx_framing_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the number of received characters with wrong parity.
+        fn rx_parity_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the number of received break conditions.
+        fn rx_break_errors(&self) -> usize {
+            0
+        }
+
+        /// Return the longest time a write had to wait for space in the TX buffer.
+        fn tx_peak_stall_time(&self) -> Duration {
+            Duration::ZERO
+        }
     }
 
     /// Trait alias for a full-fledged console.
     pub trait All: Write + Read + Statistics {}
 }
 
+/// Errors of the console subsystem.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// No space left for another console.
+    TooManyConsoles,
+
+    /// The console is not registered.
+    NotRegistered,
+
+    /// Input was aborted by the user.
+    Interrupted,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
-static CUR_CONSOLE: NullLock<&'static (dyn interface::All + Sync)> =
-    NullLock::new(&null_console::NULL_CONSOLE);
+/// The system console. The kernel log buffer is attached from the start.
+static SYSTEM_CONSOLE: mux_console::MuxConsole =
+    mux_console::MuxConsole::new(&log_buffer::KERNEL_LOG_BUFFER);
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
-use synchronization::interface::Mutex;
 
 /// Register a new console.
-pub fn register_console(new_console: &'static (dyn interface::All + Sync)) {
-    CUR_CONSOLE.lock(|con| *con = new_console);
+///
+/// The console receives all output in addition to the already registered ones, and becomes the
+/// primary console that serves input.
+pub fn register_console(new_console: &'static (dyn interface::All + Sync)) -> Result<(), Error> {
+    add_console(new_console)?;
+    set_primary_console(new_console)
 }
 
-/// Return a reference to the currently registered console.
+/// Register an additional console that only receives output.
 ///
-/// This is the global console used by all printing macros.
+/// Before being added, the console receives everything that was logged so far.
+pub fn add_console(new_console: &'static (dyn interface::All + Sync)) -> Result<(), Error> {
+    if SYSTEM_CONSOLE.contains(new_console) {
+        return Ok(());
+    }
+
+    // Best effort. The console is added regardless.
+    let _ = dump_log_buffer(new_console);
+
+    SYSTEM_CONSOLE.add(new_console)
+}
+
+/// Unregister a console.
+pub fn remove_console(console: &'static (dyn interface::All + Sync)) -> Result<(), Error> {
+    SYSTEM_CONSOLE.remove(console)
+}
+
+/// Make an already registered console the one that serves input.
+pub fn set_primary_console(console: &'static (dyn interface::All + Sync)) -> Result<(), Error> {
+    SYSTEM_CONSOLE.set_primary(console)
+}
+
+/// Return the console that serves input, if one is registered.
+///
+/// Unlike the system console, it is a single console. Output written to it reaches neither the
+/// other consoles nor the kernel log buffer.
+pub fn primary_console() -> Option<&'static (dyn interface::All + Sync)> {
+    SYSTEM_CONSOLE.primary()
+}
+
+/// Return a reference to the system console.
+///
+/// This is the global console used by all printing macros. It forwards to all registered consoles.
 pub fn console() -> &'static dyn interface::All {
-    CUR_CONSOLE.lock(|con| *con)
+    &SYSTEM_CONSOLE
+}
+
+/// Print the statistics of the system console.
+pub fn print_stats() {
+    let c = console();
+
+    info!("Console statistics:");
+    info!("      Characters written: {}", c.chars_written());
+    info!("      Characters read:    {}", c.chars_read());
+    info!(
+        "      RX errors:          {} overrun, {} framing, {} parity, {} break",
+        c.rx_overrun_errors(),
+        c.rx_framing_errors(),
+        c.rx_parity_errors(),
+        c.rx_break_errors()
+    );
+    info!(
+        "      Peak TX stall:      {}.{:06} s",
+        c.tx_peak_stall_time().as_secs(),
+        c.tx_peak_stall_time().subsec_micros()
+    );
+}
+
+/// Read a line from `console` into `buf`, with basic line editing.
+///
+/// See [`line_edit`] for the supported keys. Returns [`Error::Interrupted`] if the user aborted
+/// the line.
+///
+/// Works with any console, e.g. a specific backend or the system console returned by
+/// [`console()`]. Nothing but `console` is touched while waiting for input.
+pub fn read_line<'a>(
+    console: &(impl interface::Read + interface::Write + ?Sized),
+    buf: &'a mut [u8],
+) -> Result<&'a str, Error> {
+    // A blocking read holds the UART's lock, with IRQs masked, until a character arrives. Polling
+    // lets the timer interrupt in between.
+    let read_char = || loop {
+        if let Some(c) = console.try_read_char() {
+            break c;
+        }
+
+        cpu::nop();
+    };
+
+    line_edit::read_line(read_char, |c| console.write_char(c), buf).map_err(|_| Error::Interrupted)
+}
+
+/// Write the kernel log buffer to `target`.
+///
+/// `target` must be a specific console, not the system console returned by [`console()`].
+pub fn dump_log_buffer(target: &dyn interface::Write) -> fmt::Result {
+    log_buffer::KERNEL_LOG_BUFFER.dump(target)
 }
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! ANSI terminal helpers.
+//!
+//! Colors, cursor control and terminal size query for consoles that are connected to an ANSI
+//! terminal. All helpers output nothing unless ANSI output is enabled, which `detect()` does if
+//! the terminal answers. This way, consoles that are not connected to a terminal get plain text.
+//!
+//! The kernel runs the detection once at boot, with a short timeout so that booting without a
+//! terminal is not held up. The monitor's `term` command repeats it with a longer one.
+//!
+//! # Resources
+//!
+//! - <https://en.wikipedia.org/wiki/ANSI_escape_code>
+
+use super::interface;
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const ESC: char = '\x1b';
+
+static ANSI_ENABLED: AtomicBool = AtomicBool::new(false);
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// How long to wait for each character of the terminal's reply at boot.
+pub const BOOT_REPLY_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How long to wait for each character of the terminal's reply when the user asks for detection.
+pub const REPLY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Foreground colors.
+#[allow(missing_docs)]
+#[cfg_attr(feature = "binary_log", allow(dead_code))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Color {
+    Default,
+    Red,
+    Yellow,
+    Blue,
+    Gray,
+}
+
+/// A value that is displayed in a color.
+pub struct Colored<T> {
+    color: Color,
+    value: T,
+}
+
+/// Cursor control sequences.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum Cursor {
+    /// Move to the given position. Row and column start at 1.
+    #[allow(missing_docs)]
+    To { row: u16, column: u16 },
+
+    /// Move up by the given number of rows.
+    Up(u16),
+
+    /// Move down by the given number of rows.
+    Down(u16),
+
+    /// Move right by the given number of columns.
+    Forward(u16),
+
+    /// Move left by the given number of columns.
+    Back(u16),
+
+    /// Save the cursor position.
+    Save,
+
+    /// Restore the saved cursor position.
+    Restore,
+
+    /// Hide the cursor.
+    Hide,
+
+    /// Show the cursor.
+    Show,
+
+    /// Erase the whole screen.
+    ClearScreen,
+
+    /// Erase the line the cursor is on.
+    ClearLine,
+}
+
+/// The size of the terminal in characters.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug)]
+pub struct TerminalSize {
+    pub rows: u16,
+    pub columns: u16,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+impl Color {
+    /// The SGR parameter selecting the color.
+    const fn sgr(self) -> u8 {
+        match self {
+            Color::Default => 39,
+            Color::Red => 31,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Gray => 90,
+        }
+    }
+}
+
+/// Read the next character of the reply and check that it is `expected`.
+fn expect_char(
+    console: &(impl interface::All + ?Sized),
+    expected: char,
+    reply_timeout: Duration,
+) -> Option<()> {
+    (console.read_char_timeout(reply_timeout)? == expected).then_some(())
+}
+
+/// Read a decimal number that is terminated by `terminator`.
+fn read_number(
+    console: &(impl interface::All + ?Sized),
+    terminator: char,
+    reply_timeout: Duration,
+) -> Option<u16> {
+    let mut number: u16 = 0;
+    let mut digits = 0;
+
+    loop {
+        let c = console.read_char_timeout(reply_timeout)?;
+
+        if c == terminator && digits > 0 {
+            return Some(number);
+        }
+
+        let digit = c.to_digit(10)? as u16;
+        number = number.checked_mul(10)?.checked_add(digit)?;
+        digits += 1;
+    }
+}
+
+/// Parse a cursor position report, which has the form `ESC [ <row> ; <column> R`.
+fn read_cursor_position(
+    console: &(impl interface::All + ?Sized),
+    reply_timeout: Duration,
+) -> Option<TerminalSize> {
+    expect_char(console, ESC, reply_timeout)?;
+    expect_char(console, '[', reply_timeout)?;
+    let rows = read_number(console, ';', reply_timeout)?;
+    let columns = read_number(console, 'R', reply_timeout)?;
+
+    Some(TerminalSize { rows, columns })
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return whether ANSI output is enabled.
+pub fn is_enabled() -> bool {
+    ANSI_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Enable or disable ANSI output.
+pub fn set_enabled(enable: bool) {
+    ANSI_ENABLED.store(enable, Ordering::Relaxed);
+}
+
+/// Probe whether `console` is connected to an ANSI terminal, and return the terminal's size.
+///
+/// This moves the cursor to the bottom right corner, asks the terminal for the cursor position and
+/// moves the cursor back. ANSI output is enabled if a reply arrives, and disabled otherwise.
+///
+/// `console` should be the terminal's console itself, e.g. [`primary_console()`], not the system
+/// console. Otherwise, the query also ends up in the kernel log buffer and on the other consoles.
+///
+/// Each character of the reply must arrive within `reply_timeout`. Input that arrives before the
+/// reply is discarded.
+///
+/// [`primary_console()`]: super::primary_console
+pub fn detect(
+    console: &(impl interface::All + ?Sized),
+    reply_timeout: Duration,
+) -> Option<TerminalSize> {
+    console.clear_rx();
+
+    console.write_bytes(b"\x1b7\x1b[999;999H\x1b[6n\x1b8");
+
+    let size = read_cursor_position(console, reply_timeout);
+    set_enabled(size.is_some());
+
+    size
+}
+
+impl<T> Colored<T> {
+    /// Create an instance.
+    pub const fn new(color: Color, value: T) -> Self {
+        Self { color, value }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Colored<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !is_enabled() || self.color == Color::Default {
+            return self.value.fmt(f);
+        }
+
+        write!(f, "{}[{}m{}{}[0m", ESC, self.color.sgr(), self.value, ESC)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !is_enabled() {
+            return Ok(());
+        }
+
+        match *self {
+            Cursor::To { row, column } => write!(f, "{}[{};{}H", ESC, row, column),
+            Cursor::Up(n) => write!(f, "{}[{}A", ESC, n),
+            Cursor::Down(n) => write!(f, "{}[{}B", ESC, n),
+            Cursor::Forward(n) => write!(f, "{}[{}C", ESC, n),
+            Cursor::Back(n) => write!(f, "{}[{}D", ESC, n),
+            Cursor::Save => write!(f, "{}7", ESC),
+            Cursor::Restore => write!(f, "{}8", ESC),
+            Cursor::Hide => write!(f, "{}[?25l", ESC),
+            Cursor::Show => write!(f, "{}[?25h", ESC),
+            Cursor::ClearScreen => write!(f, "{}[2J", ESC),
+            Cursor::ClearLine => write!(f, "{}[2K", ESC),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Line discipline.
+//!
+//! Sits between the text path of a console (`read_char()`, `write_char()`) and the raw bytes on
+//! the wire. Characters are encoded to and decoded from UTF-8, and line endings are optionally
+//! translated. The raw path (`read_byte()`, `write_bytes()`) bypasses it.
+//!
+//! The line discipline is pure state, without any hardware access. Its tests run on the host, using
+//! the `host_tests` package.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Line ending handling of the text path.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineEnding {
+    /// No translation.
+    Lf,
+
+    /// `\n` is sent as `\r\n`. A received `\r` or `\r\n` is read as a single `\n`.
+    CrLf,
+}
+
+/// Receive-side state of the line discipline.
+#[derive(Copy, Clone)]
+pub struct LineDiscipline {
+    line_ending: LineEnding,
+
+    /// Bytes of a partially received UTF-8 sequence.
+    partial: [u8; 4],
+    partial_len: usize,
+
+    /// A byte that ended an invalid sequence and must be decoded on its own.
+    carry: Option<u8>,
+
+    /// The last decoded character was a `\r` that has been translated.
+    after_cr: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return the length of the UTF-8 sequence started by `byte`, or `None` if it can not start one.
+fn sequence_len(byte: u8) -> Option<usize> {
+    match byte {
+        0x00..=0x7f => Some(1),
+        0xc2..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf4 => Some(4),
+        _ => None,
+    }
+}
+
+impl LineDiscipline {
+    /// Add a byte to the partial sequence. Return the decoded character, if one is complete.
+    fn push(&mut self, byte: u8) -> Option<char> {
+        if self.partial_len == 0 {
+            let Some(len) = sequence_len(byte) else {
+                return Some(char::REPLACEMENT_CHARACTER);
+            };
+
+            if len == 1 {
+                return Some(byte as char);
+            }
+        } else if (byte & 0b1100_0000) != 0b1000_0000 {
+            // The sequence was cut short. Report it and start over with this byte.
+            self.partial_len = 0;
+            self.carry = Some(byte);
+
+            return Some(char::REPLACEMENT_CHARACTER);
+        }
+
+        self.partial[self.partial_len] = byte;
+        self.partial_len += 1;
+
+        if Some(self.partial_len) != sequence_len(self.partial[0]) {
+            return None;
+        }
+
+        let bytes = &self.partial[..self.partial_len];
+        self.partial_len = 0;
+
+        // Overlong encodings and surrogates are not caught by `sequence_len()`.
+        Some(
+            core::str::from_utf8(bytes)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        )
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl LineEnding {
+    /// Encode a character and pass the resulting bytes to `put`.
+    pub fn encode(self, c: char, mut put: impl FnMut(u8)) {
+        if self == LineEnding::CrLf && c == '\n' {
+            put(b'\r');
+        }
+
+        let mut buf = [0; 4];
+        c.encode_utf8(&mut buf)
+            .as_bytes()
+            .iter()
+            .for_each(|&b| put(b));
+    }
+}
+
+impl LineDiscipline {
+    /// Create an instance.
+    pub const fn new(line_ending: LineEnding) -> Self {
+        Self {
+            line_ending,
+            partial: [0; 4],
+            partial_len: 0,
+            carry: None,
+            after_cr: false,
+        }
+    }
+
+    /// Return the line ending in use.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Switch the line ending.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+        self.after_cr = false;
+    }
+
+    /// Decode the next character from the bytes returned by `next_byte`.
+    ///
+    /// Returns `None` once `next_byte` does, in which case a partially received character is kept
+    /// for the next call.
+    pub fn decode(&mut self, mut next_byte: impl FnMut() -> Option<u8>) -> Option<char> {
+        loop {
+            let byte = match self.carry.take() {
+                Some(x) => x,
+                None => next_byte()?,
+            };
+
+            let Some(c) = self.push(byte) else {
+                continue;
+            };
+
+            if self.line_ending == LineEnding::CrLf {
+                let after_cr = core::mem::replace(&mut self.after_cr, c == '\r');
+
+                match c {
+                    '\r' => return Some('\n'),
+                    '\n' if after_cr => continue,
+                    _ => (),
+                }
+            }
+
+            return Some(c);
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    const REPLACEMENT: char = char::REPLACEMENT_CHARACTER;
+
+    /// Decode all of `bytes`. A trailing partial character is kept in `ld`.
+    fn decode_all(ld: &mut LineDiscipline, bytes: &[u8]) -> Vec<char> {
+        let mut bytes = bytes.iter().copied();
+        let mut chars = Vec::new();
+
+        while let Some(c) = ld.decode(|| bytes.next()) {
+            chars.push(c);
+        }
+
+        chars
+    }
+
+    fn decode_lf(bytes: &[u8]) -> Vec<char> {
+        decode_all(&mut LineDiscipline::new(LineEnding::Lf), bytes)
+    }
+
+    fn decode_crlf(bytes: &[u8]) -> Vec<char> {
+        decode_all(&mut LineDiscipline::new(LineEnding::CrLf), bytes)
+    }
+
+    fn encode(line_ending: LineEnding, s: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        s.chars()
+            .for_each(|c| line_ending.encode(c, |b| bytes.push(b)));
+
+        bytes
+    }
+
+    #[test]
+    fn decodes_utf8() {
+        let s = "a\u{e9}\u{20ac}\u{1f600}";
+
+        assert_eq!(decode_lf(s.as_bytes()), s.chars().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn partial_sequence_is_carried_over() {
+        let mut ld = LineDiscipline::new(LineEnding::Lf);
+        let euro = "\u{20ac}".as_bytes();
+
+        assert_eq!(decode_all(&mut ld, &euro[..1]), vec![]);
+        assert_eq!(decode_all(&mut ld, &euro[1..2]), vec![]);
+        assert_eq!(decode_all(&mut ld, &euro[2..]), vec!['\u{20ac}']);
+    }
+
+    #[test]
+    fn invalid_bytes_are_replaced() {
+        // Not a start byte.
+        assert_eq!(decode_lf(&[0xff, b'a']), vec![REPLACEMENT, 'a']);
+        assert_eq!(decode_lf(&[0x80, b'a']), vec![REPLACEMENT, 'a']);
+
+        // Overlong encoding and surrogate.
+        assert_eq!(decode_lf(&[0xe0, 0x80, 0x80]), vec![REPLACEMENT]);
+        assert_eq!(decode_lf(&[0xed, 0xa0, 0x80]), vec![REPLACEMENT]);
+    }
+
+    #[test]
+    fn cut_short_sequence_keeps_next_byte() {
+        assert_eq!(decode_lf(&[0xe2, 0x82, b'a']), vec![REPLACEMENT, 'a']);
+
+        // The interrupting byte starts a sequence of its own.
+        let mut bytes = vec![0xe2];
+        bytes.extend_from_slice("\u{e9}".as_bytes());
+        assert_eq!(decode_lf(&bytes), vec![REPLACEMENT, '\u{e9}']);
+    }
+
+    #[test]
+    fn lf_does_not_translate() {
+        assert_eq!(decode_lf(b"a\r\nb\r"), vec!['a', '\r', '\n', 'b', '\r']);
+        assert_eq!(encode(LineEnding::Lf, "a\nb"), b"a\nb");
+    }
+
+    #[test]
+    fn crlf_translates_line_endings() {
+        assert_eq!(decode_crlf(b"a\r\nb"), vec!['a', '\n', 'b']);
+        assert_eq!(decode_crlf(b"a\rb"), vec!['a', '\n', 'b']);
+        assert_eq!(decode_crlf(b"a\nb"), vec!['a', '\n', 'b']);
+        assert_eq!(decode_crlf(b"\r\r\n\n"), vec!['\n', '\n', '\n']);
+
+        assert_eq!(
+            encode(LineEnding::CrLf, "a\nb\u{e9}"),
+            "a\r\nb\u{e9}".as_bytes()
+        );
+    }
+
+    #[test]
+    fn crlf_split_across_calls() {
+        let mut ld = LineDiscipline::new(LineEnding::CrLf);
+
+        assert_eq!(decode_all(&mut ld, b"a\r"), vec!['a', '\n']);
+        assert_eq!(decode_all(&mut ld, b"\nb"), vec!['b']);
+    }
+
+    #[test]
+    fn switching_line_ending_forgets_cr() {
+        let mut ld = LineDiscipline::new(LineEnding::CrLf);
+
+        assert_eq!(decode_all(&mut ld, b"\r"), vec!['\n']);
+        ld.set_line_ending(LineEnding::CrLf);
+        assert_eq!(decode_all(&mut ld, b"\n"), vec!['\n']);
+        assert_eq!(ld.line_ending(), LineEnding::CrLf);
+    }
+}
@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Line editing.
+//!
+//! Reads a line with the basic editing an interactive terminal user expects:
+//!
+//! - Backspace and Delete erase the last character.
+//! - Ctrl-U erases the whole line.
+//! - Ctrl-C aborts the line.
+//!
+//! Input is echoed back. Once the buffer is full, further input is rejected with a bell.
+//!
+//! The editing only needs a way to read and to echo characters, not a console. Its tests run on
+//! the host, using the `host_tests` package.
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const BACKSPACE: char = '\x08';
+const BELL: char = '\x07';
+const CTRL_C: char = '\x03';
+const CTRL_U: char = '\x15';
+const DELETE: char = '\x7f';
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// The line was aborted with Ctrl-C.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Interrupted;
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Erase the last character from the line and from the terminal.
+///
+/// Returns `false` if the line was already empty.
+fn erase_char(echo: &mut impl FnMut(char), buf: &[u8], len: &mut usize) -> bool {
+    let line = core::str::from_utf8(&buf[..*len]).unwrap_or_default();
+    let Some(c) = line.chars().next_back() else {
+        return false;
+    };
+
+    *len -= c.len_utf8();
+
+    echo(BACKSPACE);
+    echo(' ');
+    echo(BACKSPACE);
+
+    true
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Read a line into `buf` and return it without the line terminator.
+///
+/// Characters are taken from `read_char` and echoed with `echo`. The line is finished by a carriage
+/// return or newline. It is at most `buf.len()` bytes long.
+pub fn read_line(
+    mut read_char: impl FnMut() -> char,
+    mut echo: impl FnMut(char),
+    buf: &mut [u8],
+) -> Result<&str, Interrupted> {
+    let mut len = 0;
+
+    loop {
+        match read_char() {
+            '\r' | '\n' => {
+                echo('\n');
+                break;
+            }
+            CTRL_C => {
+                echo('^');
+                echo('C');
+                echo('\n');
+
+                return Err(Interrupted);
+            }
+            CTRL_U => while erase_char(&mut echo, buf, &mut len) {},
+            BACKSPACE | DELETE => {
+                if !erase_char(&mut echo, buf, &mut len) {
+                    echo(BELL);
+                }
+            }
+            c if c.is_control() => (),
+            c => {
+                if len + c.len_utf8() > buf.len() {
+                    echo(BELL);
+                    continue;
+                }
+
+                c.encode_utf8(&mut buf[len..]);
+                len += c.len_utf8();
+                echo(c);
+            }
+        }
+    }
+
+    // Only whole characters are ever stored.
+    Ok(core::str::from_utf8(&buf[..len]).unwrap_or_default())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    /// Feed `input` to `read_line()` and return the result and the echoed characters.
+    fn run(input: &str, buf_len: usize) -> (Result<String, Interrupted>, String) {
+        let mut input = input.chars();
+        let mut echoed = String::new();
+        let mut buf = std::vec![0; buf_len];
+
+        let line = read_line(
+            || input.next().expect("read past the end of the input"),
+            |c| echoed.push(c),
+            &mut buf,
+        )
+        .map(String::from);
+
+        (line, echoed)
+    }
+
+    fn line(input: &str) -> Result<String, Interrupted> {
+        run(input, 16).0
+    }
+
+    #[test]
+    fn plain_line_is_echoed() {
+        assert_eq!(run("hello\r", 16), (Ok("hello".into()), "hello\n".into()));
+        assert_eq!(line("hello\n"), Ok("hello".into()));
+        assert_eq!(line("\r"), Ok("".into()));
+    }
+
+    #[test]
+    fn backspace_and_delete_erase_last_char() {
+        assert_eq!(
+            run("ab\x08c\r", 16),
+            (Ok("ac".into()), "ab\x08 \x08c\n".into())
+        );
+        assert_eq!(line("ab\x7f\x7fc\r"), Ok("c".into()));
+
+        // Multi-byte characters are erased as a whole.
+        assert_eq!(line("a\u{20ac}\x08\r"), Ok("a".into()));
+    }
+
+    #[test]
+    fn backspace_on_empty_line_rings_bell() {
+        assert_eq!(run("\x08a\r", 16), (Ok("a".into()), "\x07a\n".into()));
+    }
+
+    #[test]
+    fn ctrl_u_erases_line() {
+        assert_eq!(line("abc\x15d\r"), Ok("d".into()));
+
+        let (_, echoed) = run("ab\x15\r", 16);
+        assert_eq!(echoed, "ab\x08 \x08\x08 \x08\n");
+    }
+
+    #[test]
+    fn ctrl_c_aborts() {
+        assert_eq!(run("ab\x03", 16), (Err(Interrupted), "ab^C\n".into()));
+    }
+
+    #[test]
+    fn other_control_chars_are_ignored() {
+        assert_eq!(run("a\x1bb\r", 16), (Ok("ab".into()), "ab\n".into()));
+    }
+
+    #[test]
+    fn full_buffer_rejects_input() {
+        assert_eq!(run("abcd\r", 3), (Ok("abc".into()), "abc\x07\n".into()));
+
+        // A multi-byte character that does not fit anymore is rejected as a whole.
+        assert_eq!(
+            run("ab\u{20ac}c\r", 3),
+            (Ok("abc".into()), "ab\x07c\n".into())
+        );
+    }
+
+    #[test]
+    fn multi_byte_chars() {
+        assert_eq!(
+            line("\u{e9}\u{20ac}\u{1f600}\r"),
+            Ok("\u{e9}\u{20ac}\u{1f600}".into())
+        );
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Kernel log buffer.
+//!
+//! An in-memory console that keeps the most recent `LOG_BUFFER_SIZE` bytes of output. It is part
+//! of the system console from the start, so it also captures everything that is printed before a
+//! real console is registered.
+//!
+//! Raw bytes written with `write_bytes()` are dropped, except with the `binary_log` feature, where
+//! they carry the log frames. ANSI escape sequences in text are dropped as well. Replaying them
+//! later would move the cursor or change colors of a terminal that might not even be the one they
+//! were meant for.
+//!
+//! With a debugger attached, the buffer can be inspected through the `KERNEL_LOG_BUFFER` symbol.
+
+mod inner;
+
+use super::interface;
+use crate::synchronization::{interface::Mutex, IRQSafeLock};
+use core::fmt;
+use inner::LogBufferInner;
+
+#[cfg(feature = "binary_log")]
+use crate::print::binary;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const LOG_BUFFER_SIZE: usize = 4096;
+
+/// How much of the buffer is copied out at a time by [`LogBuffer::dump()`].
+const DUMP_CHUNK_SIZE: usize = 128;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A ring buffer holding the kernel log.
+pub struct LogBuffer {
+    inner: IRQSafeLock<LogBufferInner<LOG_BUFFER_SIZE>>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+#[no_mangle]
+pub static KERNEL_LOG_BUFFER: LogBuffer = LogBuffer::new();
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl LogBuffer {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            inner: IRQSafeLock::new(LogBufferInner::new()),
+        }
+    }
+
+    /// Write the buffered log to `target`.
+    ///
+    /// The log is copied out in small chunks, and `target` is written to without holding the lock.
+    /// Hence, interrupts are not masked for long, and `target` may print itself. Only what was
+    /// buffered when the dump started is written.
+    ///
+    /// With the `binary_log` feature, the buffer holds log frames, which are written as raw bytes.
+    /// What is left of a frame whose start was overwritten is skipped.
+    #[cfg(feature = "binary_log")]
+    pub fn dump(&self, target: &dyn interface::Write) -> fmt::Result {
+        let end = self.inner.lock(|inner| inner.bytes_written());
+
+        inner::dump_frames(
+            end,
+            &mut [0; DUMP_CHUNK_SIZE],
+            binary::FRAME_START,
+            |pos, chunk| self.inner.lock(|inner| inner.copy_out(pos, end, chunk)),
+            |bytes| target.write_bytes(bytes),
+        );
+
+        Ok(())
+    }
+
+    /// Write the buffered log to `target`.
+    ///
+    /// The log is copied out in small chunks, and `target` is written to without holding the lock.
+    /// Hence, interrupts are not masked for long, and `target` may print itself. Only what was
+    /// buffered when the dump started is written.
+    #[cfg(not(feature = "binary_log"))]
+    pub fn dump(&self, target: &dyn interface::Write) -> fmt::Result {
+        let end = self.inner.lock(|inner| inner.bytes_written());
+
+        inner::dump_text(
+            end,
+            &mut [0; DUMP_CHUNK_SIZE],
+            |pos, chunk| self.inner.lock(|inner| inner.copy_out(pos, end, chunk)),
+            |text| target.write_fmt(format_args!("{}", text)),
+        )
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::Write for LogBuffer {
+    fn write_char(&self, c: char) {
+        self.inner
+            .lock(|inner| fmt::Write::write_char(inner, c))
+            .unwrap()
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        self.inner.lock(|inner| fmt::Write::write_fmt(inner, args))
+    }
+
+    #[cfg(feature = "binary_log")]
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.inner.lock(|inner| inner.push_bytes(bytes))
+    }
+
+    #[cfg(not(feature = "binary_log"))]
+    fn write_bytes(&self, _bytes: &[u8]) {}
+
+    fn flush(&self) {}
+}
+
+impl interface::Read for LogBuffer {
+    fn clear_rx(&self) {}
+}
+
+impl interface::Statistics for LogBuffer {
+    fn chars_written(&self) -> usize {
+        self.inner.lock(|inner| inner.chars_written())
+    }
+}
+
+impl interface::All for LogBuffer {}
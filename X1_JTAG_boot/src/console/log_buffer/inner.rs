@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! The ring buffer behind the kernel log buffer, without the lock.
+//!
+//! Also holds the chunked dumping of the buffer's content. The lock is only taken by the callers'
+//! closures, so nothing in here depends on the rest of the kernel. Its tests run on the host, using
+//! the `host_tests` package.
+
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Progress through an ANSI escape sequence.
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Escape {
+    None,
+    Started,
+    ControlSequence,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A ring buffer keeping the last `SIZE` bytes pushed to it.
+///
+/// Text written through [`fmt::Write`] is stored without ANSI escape sequences.
+pub struct LogBufferInner<const SIZE: usize> {
+    buf: [u8; SIZE],
+
+    /// Number of bytes pushed so far. The next byte is stored at index `bytes_written % SIZE`.
+    bytes_written: usize,
+    chars_written: usize,
+
+    /// Escape sequences can be split across writes.
+    escape: Escape,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<const SIZE: usize> LogBufferInner<SIZE> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; SIZE],
+            bytes_written: 0,
+            chars_written: 0,
+            escape: Escape::None,
+        }
+    }
+
+    /// The number of bytes pushed so far, including overwritten ones.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+
+    /// The number of characters written as text so far, without escape sequences.
+    pub fn chars_written(&self) -> usize {
+        self.chars_written
+    }
+
+    /// Push raw bytes, overwriting the oldest ones once the buffer is full.
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buf[self.bytes_written % SIZE] = b;
+            self.bytes_written += 1;
+        }
+    }
+
+    /// Copy the bytes from position `from` up to `to` into `chunk`, as many as fit.
+    ///
+    /// Positions count all bytes ever pushed. If `from` was overwritten already, copying starts at
+    /// the oldest byte instead. Returns the position of the first copied byte and the number of
+    /// copied bytes.
+    pub fn copy_out(&self, from: usize, to: usize, chunk: &mut [u8]) -> (usize, usize) {
+        let from = from.max(self.bytes_written.saturating_sub(SIZE));
+        let len = chunk.len().min(to.saturating_sub(from));
+
+        for (i, b) in chunk[..len].iter_mut().enumerate() {
+            *b = self.buf[(from + i) % SIZE];
+        }
+
+        (from, len)
+    }
+}
+
+impl<const SIZE: usize> fmt::Write for LogBufferInner<SIZE> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.escape = match (self.escape, c) {
+                (Escape::None, '\x1b') => Escape::Started,
+                (Escape::None, c) => {
+                    self.push_bytes(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    self.chars_written += 1;
+
+                    Escape::None
+                }
+                // Control sequences end with a byte in the range 0x40..=0x7e. Everything else is
+                // a two character sequence, like `ESC 7`.
+                (Escape::Started, '[') => Escape::ControlSequence,
+                (Escape::Started, _) => Escape::None,
+                (Escape::ControlSequence, '\x40'..='\x7e') => Escape::None,
+                (Escape::ControlSequence, _) => Escape::ControlSequence,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Dump the text pushed before position `end`, one `chunk` at a time.
+///
+/// `copy_out` fills the chunk, like [`LogBufferInner::copy_out()`] does up to `end`. `write` is
+/// called with the text of every chunk. What is left of a character whose start was overwritten
+/// is skipped, and a character cut in two by the chunk size is written with the next chunk. Hence,
+/// the chunk must hold at least four bytes, the length of the longest character.
+#[cfg(any(test, not(feature = "binary_log")))]
+pub fn dump_text(
+    end: usize,
+    chunk: &mut [u8],
+    mut copy_out: impl FnMut(usize, &mut [u8]) -> (usize, usize),
+    mut write: impl FnMut(&str) -> fmt::Result,
+) -> fmt::Result {
+    let mut pos = 0;
+
+    while pos < end {
+        let (from, len) = copy_out(pos, chunk);
+        if len == 0 {
+            break;
+        }
+        let bytes = &chunk[..len];
+
+        // Skip continuation bytes at the start.
+        let skip = bytes
+            .iter()
+            .position(|b| (b & 0b1100_0000) != 0b1000_0000)
+            .unwrap_or(len);
+
+        // Only whole strings are pushed, so invalid sequences are not expected. Be defensive
+        // anyways, and always make progress.
+        let valid = match core::str::from_utf8(&bytes[skip..]) {
+            Ok(s) => s.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let text = core::str::from_utf8(&bytes[skip..(skip + valid)]).unwrap_or_default();
+        write(text)?;
+
+        pos = from + (skip + valid).max(1);
+    }
+
+    Ok(())
+}
+
+/// Dump the binary log frames pushed before position `end`, one `chunk` at a time.
+///
+/// Like [`dump_text()`], but `write` gets the raw bytes. If the oldest bytes were overwritten,
+/// everything up to the next `frame_start` byte is skipped, which is what is left of a frame whose
+/// start was overwritten.
+#[cfg(any(test, feature = "binary_log"))]
+pub fn dump_frames(
+    end: usize,
+    chunk: &mut [u8],
+    frame_start: u8,
+    mut copy_out: impl FnMut(usize, &mut [u8]) -> (usize, usize),
+    mut write: impl FnMut(&[u8]),
+) {
+    let mut pos = 0;
+    let mut synced = false;
+
+    while pos < end {
+        let (from, len) = copy_out(pos, chunk);
+        if len == 0 {
+            break;
+        }
+        let mut bytes = &chunk[..len];
+
+        // If nothing was overwritten, the buffer starts with a whole frame or with text.
+        if !synced {
+            let skip = match from {
+                0 => 0,
+                _ => bytes.iter().position(|&b| b == frame_start).unwrap_or(len),
+            };
+            bytes = &bytes[skip..];
+            synced = !bytes.is_empty();
+        }
+
+        write(bytes);
+        pos = from + len;
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fmt::Write;
+
+    /// Dump `inner` as text with a chunk of `chunk_size` bytes. Returns the written pieces.
+    fn text<const SIZE: usize>(inner: &LogBufferInner<SIZE>, chunk_size: usize) -> Vec<String> {
+        let mut pieces = Vec::new();
+        let end = inner.bytes_written();
+
+        dump_text(
+            end,
+            &mut vec![0; chunk_size],
+            |pos, chunk| inner.copy_out(pos, end, chunk),
+            |text| {
+                pieces.push(text.to_string());
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        pieces
+    }
+
+    fn frames<const SIZE: usize>(inner: &LogBufferInner<SIZE>, chunk_size: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let end = inner.bytes_written();
+
+        dump_frames(
+            end,
+            &mut vec![0; chunk_size],
+            0xfe,
+            |pos, chunk| inner.copy_out(pos, end, chunk),
+            |x| bytes.extend_from_slice(x),
+        );
+
+        bytes
+    }
+
+    #[test]
+    fn ring_keeps_the_newest_bytes() {
+        let mut inner = LogBufferInner::<8>::new();
+
+        inner.push_bytes(b"abcdef");
+        assert_eq!(text(&inner, 16), ["abcdef"]);
+
+        inner.push_bytes(b"ghijk");
+        assert_eq!(inner.bytes_written(), 11);
+        assert_eq!(text(&inner, 16), ["defghijk"]);
+
+        // Exactly one lap.
+        inner.push_bytes(b"lmnop");
+        assert_eq!(text(&inner, 16), ["ijklmnop"]);
+    }
+
+    #[test]
+    fn copy_out_skips_overwritten_positions() {
+        let mut inner = LogBufferInner::<4>::new();
+        inner.push_bytes(b"abcdef");
+
+        let mut chunk = [0; 8];
+        assert_eq!(inner.copy_out(0, 6, &mut chunk), (2, 4));
+        assert_eq!(&chunk[..4], b"cdef");
+
+        // Stops at `to`, and at the chunk size.
+        assert_eq!(inner.copy_out(3, 5, &mut chunk), (3, 2));
+        assert_eq!(&chunk[..2], b"de");
+        assert_eq!(inner.copy_out(2, 6, &mut chunk[..3]), (2, 3));
+        assert_eq!(inner.copy_out(6, 6, &mut chunk), (6, 0));
+    }
+
+    #[test]
+    fn escape_sequences_are_dropped() {
+        let mut inner = LogBufferInner::<64>::new();
+
+        write!(inner, "a\x1b[31mb\x1b7c\x1b[").unwrap();
+        write!(inner, "0md").unwrap();
+
+        assert_eq!(text(&inner, 64), ["abcd"]);
+        assert_eq!(inner.chars_written(), 4);
+    }
+
+    #[test]
+    fn characters_split_by_chunks_are_kept_whole() {
+        let mut inner = LogBufferInner::<64>::new();
+        write!(inner, "aäb€c").unwrap();
+
+        assert_eq!(text(&inner, 3), ["aä", "b", "€", "c"]);
+        assert_eq!(text(&inner, 4), ["aäb", "€c"]);
+    }
+
+    #[test]
+    fn overwritten_character_start_is_skipped() {
+        let mut inner = LogBufferInner::<6>::new();
+
+        // `€` is three bytes. Only its last one is left after the wrap.
+        write!(inner, "€abcde").unwrap();
+
+        assert_eq!(text(&inner, 4).concat(), "abcde");
+    }
+
+    #[test]
+    fn invalid_bytes_make_progress() {
+        let mut inner = LogBufferInner::<16>::new();
+        inner.push_bytes(b"a\xffb");
+
+        assert_eq!(text(&inner, 16).concat(), "ab");
+    }
+
+    #[test]
+    fn frames_resync_after_wrap() {
+        let mut inner = LogBufferInner::<8>::new();
+
+        inner.push_bytes(&[0xfe, 1, 2]);
+        assert_eq!(frames(&inner, 4), [0xfe, 1, 2]);
+
+        // The first frame's start is overwritten, so its rest is skipped.
+        inner.push_bytes(&[0xfe, 3, 4, 5, 0xfe, 6]);
+        assert_eq!(frames(&inner, 4), [0xfe, 3, 4, 5, 0xfe, 6]);
+    }
+
+    #[test]
+    fn frames_without_start_in_first_chunk() {
+        let mut inner = LogBufferInner::<8>::new();
+
+        inner.push_bytes(&[0xfe, 1, 2, 3, 4, 5, 6, 7, 8, 0xfe, 9]);
+        assert_eq!(frames(&inner, 2), [0xfe, 9]);
+    }
+}
@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Console multiplexer.
+//!
+//! Fans all output out to every registered backend console. Input and statistics are taken from a
+//! single primary backend. Without any backend, everything is silently dropped.
+
+use super::{interface, Error, LineEnding};
+use crate::synchronization::{interface::Mutex, IRQSafeLock};
+use core::{fmt, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_CONSOLES: usize = 4;
+
+#[derive(Copy, Clone)]
+struct MuxConsoleInner {
+    backends: [Option<Backend>; MAX_CONSOLES],
+    primary: Option<usize>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A console the multiplexer can forward to.
+pub type Backend = &'static (dyn interface::All + Sync);
+
+/// A console that forwards to up to `MAX_CONSOLES` backend consoles.
+pub struct MuxConsole {
+    inner: IRQSafeLock<MuxConsoleInner>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Compare backends by address only. Comparing the vtable part of the fat pointers is unreliable.
+fn is_same(a: Backend, b: Backend) -> bool {
+    core::ptr::eq(a as *const _ as *const (), b as *const _ as *const ())
+}
+
+impl MuxConsoleInner {
+    const fn new(backend: Backend) -> Self {
+        let mut backends = [None; MAX_CONSOLES];
+        backends[0] = Some(backend);
+
+        Self {
+            backends,
+            primary: None,
+        }
+    }
+
+    fn position(&self, backend: Backend) -> Option<usize> {
+        self.backends
+            .iter()
+            .position(|x| matches!(x, Some(x) if is_same(*x, backend)))
+    }
+
+    fn primary(&self) -> Option<Backend> {
+        self.primary.and_then(|i| self.backends[i])
+    }
+
+    fn backends(&self) -> impl Iterator<Item = Backend> + '_ {
+        self.backends.iter().flatten().copied()
+    }
+}
+
+impl MuxConsole {
+    /// Take a snapshot of the registered backends.
+    ///
+    /// The backends are called without holding the lock, so that slow output does not keep
+    /// interrupts masked and a panic inside a backend can still print.
+    fn snapshot(&self) -> MuxConsoleInner {
+        self.inner.lock(|inner| *inner)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl MuxConsole {
+    /// Create an instance with a first backend, which is not the primary.
+    pub const fn new(backend: Backend) -> Self {
+        Self {
+            inner: IRQSafeLock::new(MuxConsoleInner::new(backend)),
+        }
+    }
+
+    /// Return whether the backend is registered.
+    pub fn contains(&self, backend: Backend) -> bool {
+        self.inner.lock(|inner| inner.position(backend).is_some())
+    }
+
+    /// Add a backend. Adding an already registered backend is a no-op.
+    pub fn add(&self, backend: Backend) -> Result<(), Error> {
+        self.inner.lock(|inner| {
+            if inner.position(backend).is_some() {
+                return Ok(());
+            }
+
+            let slot = inner
+                .backends
+                .iter_mut()
+                .find(|x| x.is_none())
+                .ok_or(Error::TooManyConsoles)?;
+            *slot = Some(backend);
+
+            Ok(())
+        })
+    }
+
+    /// Remove a backend. If it was the primary, there is no primary afterwards.
+    pub fn remove(&self, backend: Backend) -> Result<(), Error> {
+        self.inner.lock(|inner| {
+            let i = inner.position(backend).ok_or(Error::NotRegistered)?;
+
+            inner.backends[i] = None;
+            if inner.primary == Some(i) {
+                inner.primary = None;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Return the backend that serves reads and statistics, if any.
+    pub fn primary(&self) -> Option<Backend> {
+        self.snapshot().primary()
+    }
+
+    /// Select the backend that serves reads and statistics.
+    pub fn set_primary(&self, backend: Backend) -> Result<(), Error> {
+        self.inner.lock(|inner| {
+            inner.primary = Some(inner.position(backend).ok_or(Error::NotRegistered)?);
+
+            Ok(())
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl interface::Write for MuxConsole {
+    fn write_char(&self, c: char) {
+        self.snapshot().backends().for_each(|x| x.write_char(c));
+    }
+
+    fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        // Write to every backend, even if an earlier one failed.
+        let mut result = Ok(());
+        for x in self.snapshot().backends() {
+            result = result.and(x.write_fmt(args));
+        }
+
+        result
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) {
+        self.snapshot()
+            .backends()
+            .for_each(|x| x.write_bytes(bytes));
+    }
+
+    fn set_line_ending(&self, line_ending: LineEnding) {
+        self.snapshot()
+            .backends()
+            .for_each(|x| x.set_line_ending(line_ending));
+    }
+
+    fn flush(&self) {
+        self.snapshot().backends().for_each(|x| x.flush());
+    }
+}
+
+impl interface::Read for MuxConsole {
+    fn read_char(&self) -> char {
+        match self.snapshot().primary() {
+            Some(x) => x.read_char(),
+            None => ' ',
+        }
+    }
+
+    fn try_read_char(&self) -> Option<char> {
+        self.snapshot().primary()?.try_read_char()
+    }
+
+    fn read_byte(&self) -> u8 {
+        match self.snapshot().primary() {
+            Some(x) => x.read_byte(),
+            None => 0,
+        }
+    }
+
+    fn clear_rx(&self) {
+        self.snapshot().backends().for_each(|x| x.clear_rx());
+    }
+}
+
+impl interface::Statistics for MuxConsole {
+    fn chars_written(&self) -> usize {
+        self.snapshot().primary().map_or(0, |x| x.chars_written())
+    }
+
+    fn chars_read(&self) -> usize {
+        self.snapshot().primary().map_or(0, |x| x.chars_read())
+    }
+
+    fn rx_overrun_errors(&self) -> usize {
+        self.snapshot()
+            .primary()
+            .map_or(0, |x| x.rx_overrun_errors())
+    }
+
+    fn rx_framing_errors(&self) -> usize {
+        self.snapshot()
+            .primary()
+            .map_or(0, |x| x.rx_framing_errors())
+    }
+
+    fn rx_parity_errors(&self) -> usize {
+        self.snapshot()
+            .primary()
+            .map_or(0, |x| x.rx_parity_errors())
+    }
+
+    fn rx_break_errors(&self) -> usize {
+        self.snapshot().primary().map_or(0, |x| x.rx_break_errors())
+    }
+
+    fn tx_peak_stall_time(&self) -> Duration {
+        self.snapshot()
+            .primary()
+            .map_or(Duration::ZERO, |x| x.tx_peak_stall_time())
+    }
+}
+
+impl interface::All for MuxConsole {}
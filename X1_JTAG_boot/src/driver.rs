@@ -4,17 +4,28 @@
 
 //! Driver support.
 
-use crate::synchronization::{interface::Mutex, NullLock};
+mod dependency_sort;
+
+use crate::{
+    bsp, debug, info,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+    warn,
+};
+use core::fmt;
 
 //--------------------------------------------------------------------------------------------------
 // Private Definitions
 //--------------------------------------------------------------------------------------------------
 
-const NUM_DRIVERS: usize = 5;
+#[derive(Copy, Clone)]
+struct RegisteredDriver {
+    descriptor: DeviceDriverDescriptor,
+    state: DriverState,
+}
 
-struct DriverManagerInner {
+struct DriverManagerInner<const NUM_DRIVERS: usize> {
     next_index: usize,
-    descriptors: [Option<DeviceDriverDescriptor>; NUM_DRIVERS],
+    drivers: [Option<RegisteredDriver>; NUM_DRIVERS],
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -23,6 +34,8 @@ struct DriverManagerInner {
 
 /// Driver interfaces.
 pub mod interface {
+    use super::Error;
+
     /// Device Driver functions.
     pub trait DeviceDriver {
         /// Return a compatibility string for identifying the driver.
@@ -33,44 +46,179 @@ pub mod interface {
         /// # Safety
         ///
         /// - During init, drivers might do stuff with system-wide impact.
-        unsafe fn init(&self) -> Result<(), &'static str> {
+        unsafe fn init(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        /// Called by the kernel to put the device back into a clean state, e.g. before handing
+        /// off to the next boot stage.
+        ///
+        /// # Safety
+        ///
+        /// - The device must not be used anymore after this was called.
+        unsafe fn deinit(&self) -> Result<(), Error> {
             Ok(())
         }
     }
 }
 
+/// Errors of the driver subsystem.
+// Not every kind is produced by the in-tree drivers; the rest is there for drivers to report.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The driver or subsystem was already initialized.
+    AlreadyInitialized,
+
+    /// The driver registry has no space left.
+    TooManyDrivers,
+
+    /// A driver with the same compatible string is registered already.
+    AlreadyRegistered,
+
+    /// The device did not react as expected.
+    HardwareNotResponding,
+
+    /// The device or driver configuration is invalid.
+    InvalidConfiguration,
+
+    /// A dependency of the driver is not registered.
+    UnknownDependency,
+
+    /// The dependencies of the driver form a cycle.
+    DependencyCycle,
+
+    /// A dependency of the driver is not up.
+    DependencyNotUp,
+
+    /// A driver specific error, with a description.
+    ///
+    /// The name of the reporting driver is added by the driver manager, see [`DriverError`].
+    Custom(&'static str),
+}
+
+/// An [`Error`] annotated with the driver it originates from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DriverError {
+    /// The compatible string of the failing driver.
+    pub compatible: &'static str,
+
+    /// What went wrong.
+    pub kind: Error,
+}
+
 /// Tpye to be used as an optional callback after a driver's init() has run.
-pub type DeviceDriverPostInitCallback = unsafe fn() -> Result<(), &'static str>;
+pub type DeviceDriverPostInitCallback = unsafe fn() -> Result<(), Error>;
 
 /// A descriptor for device drivers.
 #[derive(Copy, Clone)]
 pub struct DeviceDriverDescriptor {
     device_driver: &'static (dyn interface::DeviceDriver + Sync),
     post_init_callback: Option<DeviceDriverPostInitCallback>,
+    critical: bool,
+    dependencies: &'static [&'static str],
+}
+
+/// The state of a registered driver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DriverState {
+    /// Registered, but `init_drivers()` did not get to it yet.
+    Registered,
+
+    /// Successfully initialized, including the post-init callback.
+    Up,
+
+    /// The driver's init or post-init callback failed.
+    Failed(Error),
+
+    /// Not initialized because a critical driver failed before, or because one of its
+    /// dependencies is not up.
+    Skipped,
 }
 
 /// Provides device driver management functions.
-pub struct DriverManager {
-    inner: NullLock<DriverManagerInner>,
+///
+/// Up to `NUM_DRIVERS` drivers can be registered.
+pub struct DriverManager<const NUM_DRIVERS: usize> {
+    inner: InitStateLock<DriverManagerInner<NUM_DRIVERS>>,
 }
 
+/// The type of the global DriverManager, sized by the BSP.
+pub type KernelDriverManager = DriverManager<{ bsp::driver::NUM_DRIVERS }>;
+
 //--------------------------------------------------------------------------------------------------
 // Global instances
 //--------------------------------------------------------------------------------------------------
 
-static DRIVER_MANAGER: DriverManager = DriverManager::new();
+static DRIVER_MANAGER: KernelDriverManager = DriverManager::new();
 
 //--------------------------------------------------------------------------------------------------
 // Private Code
 //--------------------------------------------------------------------------------------------------
 
-impl DriverManagerInner {
+impl<const NUM_DRIVERS: usize> DriverManagerInner<NUM_DRIVERS> {
     /// Create an instance.
     pub const fn new() -> Self {
         Self {
             next_index: 0,
-            descriptors: [None; NUM_DRIVERS],
+            drivers: [None; NUM_DRIVERS],
+        }
+    }
+
+    fn registered(&self) -> impl DoubleEndedIterator<Item = &RegisteredDriver> {
+        self.drivers[..self.next_index]
+            .iter()
+            .filter_map(|x| x.as_ref())
+    }
+
+    fn find(&self, compatible: &str) -> Option<&RegisteredDriver> {
+        self.registered()
+            .find(|x| x.descriptor.device_driver.compatible() == compatible)
+    }
+
+    /// Return whether all dependencies of `descriptor` are up.
+    fn dependencies_up(&self, descriptor: &DeviceDriverDescriptor) -> bool {
+        descriptor
+            .dependencies
+            .iter()
+            .all(|dep| matches!(self.find(dep), Some(x) if x.state == DriverState::Up))
+    }
+
+    /// Reorder the registered drivers so that every driver comes after its dependencies.
+    ///
+    /// Among the drivers whose dependencies are satisfied, registration order is kept.
+    fn sort_by_dependencies(&mut self) -> Result<(), DriverError> {
+        let num_drivers = self.next_index;
+        let name =
+            |i: usize| self.drivers[i].map_or("", |x| x.descriptor.device_driver.compatible());
+        let dependencies =
+            |i: usize| self.drivers[i].map_or(&[] as &[&str], |x| x.descriptor.dependencies);
+
+        let order: [usize; NUM_DRIVERS] = dependency_sort::sort_by_dependencies(
+            num_drivers,
+            name,
+            dependencies,
+        )
+        .map_err(|e| {
+            let (i, kind) = match e {
+                dependency_sort::Error::UnknownDependency(i) => (i, Error::UnknownDependency),
+                dependency_sort::Error::DependencyCycle(i) => (i, Error::DependencyCycle),
+            };
+
+            DriverError {
+                compatible: name(i),
+                kind,
+            }
+        })?;
+
+        let mut sorted: [Option<RegisteredDriver>; NUM_DRIVERS] = [None; NUM_DRIVERS];
+        for (next, &i) in sorted.iter_mut().zip(&order[..num_drivers]) {
+            *next = self.drivers[i];
         }
+
+        self.drivers = sorted;
+
+        Ok(())
     }
 }
 
@@ -78,77 +226,248 @@ impl DriverManagerInner {
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyInitialized => write!(f, "Already initialized"),
+            Self::TooManyDrivers => write!(f, "Too many drivers"),
+            Self::AlreadyRegistered => write!(f, "Already registered"),
+            Self::HardwareNotResponding => write!(f, "Hardware not responding"),
+            Self::InvalidConfiguration => write!(f, "Invalid configuration"),
+            Self::UnknownDependency => write!(f, "Unknown dependency"),
+            Self::DependencyCycle => write!(f, "Dependency cycle"),
+            Self::DependencyNotUp => write!(f, "Dependency not up"),
+            Self::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.compatible, self.kind)
+    }
+}
+
+impl fmt::Display for DriverState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Registered => write!(f, "Registered"),
+            Self::Up => write!(f, "Up"),
+            Self::Failed(x) => write!(f, "Failed ({})", x),
+            Self::Skipped => write!(f, "Skipped"),
+        }
+    }
+}
+
 impl DeviceDriverDescriptor {
     /// Create an instance.
+    ///
+    /// If a `critical` driver fails to initialize, the kernel cannot continue booting. Failures of
+    /// non-critical drivers are only reported and the driver is left out.
+    ///
+    /// `dependencies` lists the compatible strings of drivers that must be up before this one is
+    /// initialized.
     pub fn new(
         device_driver: &'static (dyn interface::DeviceDriver + Sync),
         post_init_callback: Option<DeviceDriverPostInitCallback>,
+        critical: bool,
+        dependencies: &'static [&'static str],
     ) -> Self {
         Self {
             device_driver,
             post_init_callback,
+            critical,
+            dependencies,
+        }
+    }
+
+    /// Run the driver's init and post-init callback.
+    ///
+    /// # Safety
+    ///
+    /// - See [`interface::DeviceDriver::init`].
+    unsafe fn init(&self) -> Result<(), Error> {
+        // 1. Initialize driver.
+        self.device_driver.init()?;
+
+        // 2. Call corresponding post init callback.
+        if let Some(callback) = &self.post_init_callback {
+            callback()?;
         }
+
+        Ok(())
     }
 }
 
 /// Return a reference to the global DriverManager.
-pub fn driver_manager() -> &'static DriverManager {
+pub fn driver_manager() -> &'static KernelDriverManager {
     &DRIVER_MANAGER
 }
 
-impl DriverManager {
+impl<const NUM_DRIVERS: usize> DriverManager<NUM_DRIVERS> {
     /// Create an instance.
     pub const fn new() -> Self {
         Self {
-            inner: NullLock::new(DriverManagerInner::new()),
+            inner: InitStateLock::new(DriverManagerInner::new()),
         }
     }
 
     /// Register a device driver with the kernel.
-    pub fn register_driver(&self, descriptor: DeviceDriverDescriptor) {
-        self.inner.lock(|inner| {
-            inner.descriptors[inner.next_index] = Some(descriptor);
+    ///
+    /// Only possible during the kernel's init phase. Fails with [`Error::TooManyDrivers`] if all
+    /// `NUM_DRIVERS` slots are taken. Compatible strings must be unique.
+    pub fn register_driver(&self, descriptor: DeviceDriverDescriptor) -> Result<(), Error> {
+        if self.find(descriptor.device_driver.compatible()).is_some() {
+            return Err(Error::AlreadyRegistered);
+        }
+
+        self.inner.write(|inner| {
+            let slot = inner
+                .drivers
+                .get_mut(inner.next_index)
+                .ok_or(Error::TooManyDrivers)?;
+
+            *slot = Some(RegisteredDriver {
+                descriptor,
+                state: DriverState::Registered,
+            });
             inner.next_index += 1;
+
+            Ok(())
         })
     }
 
-    /// Helper for iterating over registered drivers.
-    fn for_each_descriptor<'a>(&'a self, f: impl FnMut(&'a DeviceDriverDescriptor)) {
-        self.inner.lock(|inner| {
-            inner
-                .descriptors
-                .iter()
-                .filter_map(|x| x.as_ref())
-                .for_each(f)
+    fn set_state(&self, index: usize, state: DriverState) {
+        self.inner.write(|inner| {
+            if let Some(driver) = &mut inner.drivers[index] {
+                driver.state = state;
+            }
         })
     }
 
     /// Fully initialize all drivers.
     ///
+    /// Drivers are initialized after their dependencies. Unknown or cyclic dependencies are
+    /// reported as an error before any driver is touched.
+    ///
+    /// If a non-critical driver fails, a warning is printed, the driver is marked as failed, and
+    /// init continues with the next driver. Drivers depending on it are skipped. If a critical
+    /// driver fails or must be skipped, all remaining drivers are skipped and the error is
+    /// returned.
+    ///
     /// # Safety
     ///
     /// - During init, drivers might do stuff with system-wide impact.
-    pub unsafe fn init_drivers(&self) {
-        self.for_each_descriptor(|descriptor| {
-            // 1. Initialize driver.
-            if let Err(x) = descriptor.device_driver.init() {
-                panic!(
-                    "Error initializing driver: {}: {}",
-                    descriptor.device_driver.compatible(),
-                    x
-                );
+    pub unsafe fn init_drivers(&self) -> Result<(), DriverError> {
+        self.inner.write(|inner| inner.sort_by_dependencies())?;
+
+        let num_drivers = self.inner.read(|inner| inner.next_index);
+
+        for index in 0..num_drivers {
+            // Copy the descriptor out, so that no lock is held while the driver code runs.
+            let Some(driver) = self.inner.read(|inner| inner.drivers[index]) else {
+                continue;
+            };
+            let descriptor = driver.descriptor;
+            debug!(
+                "Initializing driver: {}",
+                descriptor.device_driver.compatible()
+            );
+
+            let result = if self.inner.read(|inner| inner.dependencies_up(&descriptor)) {
+                descriptor.init()
+            } else {
+                Err(Error::DependencyNotUp)
+            };
+
+            let kind = match result {
+                Ok(()) => {
+                    self.set_state(index, DriverState::Up);
+                    continue;
+                }
+                Err(x) => x,
+            };
+
+            let state = if kind == Error::DependencyNotUp {
+                DriverState::Skipped
+            } else {
+                DriverState::Failed(kind)
+            };
+            self.set_state(index, state);
+
+            let compatible = descriptor.device_driver.compatible();
+            if descriptor.critical {
+                for remaining in (index + 1)..num_drivers {
+                    self.set_state(remaining, DriverState::Skipped);
+                }
+
+                return Err(DriverError { compatible, kind });
             }
 
-            // 2. Call corresponding post init callback.
-            if let Some(callback) = &descriptor.post_init_callback {
-                if let Err(x) = callback() {
-                    panic!(
-                        "Error during driver post-init callback: {}: {}",
-                        descriptor.device_driver.compatible(),
-                        x
-                    );
+            warn!(
+                "Error initializing non-critical driver: {}: {}. Continuing without it",
+                compatible, kind
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Call `f` with every registered driver and its current state.
+    pub fn for_each_driver(
+        &self,
+        mut f: impl FnMut(&'static (dyn interface::DeviceDriver + Sync), DriverState),
+    ) {
+        self.inner.read(|inner| {
+            inner
+                .drivers
+                .iter()
+                .filter_map(|x| x.as_ref())
+                .for_each(|driver| f(driver.descriptor.device_driver, driver.state))
+        })
+    }
+
+    /// Shut down all drivers that are up, in reverse init order.
+    ///
+    /// Errors do not stop the shutdown of the remaining drivers. The first one is returned.
+    ///
+    /// # Safety
+    ///
+    /// - The shut down devices must not be used anymore afterwards, which includes printing.
+    pub unsafe fn shutdown_drivers(&self) -> Result<(), DriverError> {
+        self.inner.read(|inner| {
+            let mut result = Ok(());
+
+            for driver in inner.registered().rev() {
+                if driver.state != DriverState::Up {
+                    continue;
+                }
+
+                let device_driver = driver.descriptor.device_driver;
+                if let Err(kind) = device_driver.deinit() {
+                    result = result.and(Err(DriverError {
+                        compatible: device_driver.compatible(),
+                        kind,
+                    }));
                 }
             }
+
+            result
+        })
+    }
+
+    /// Return the registered driver with the given compatible string.
+    pub fn find(&self, compatible: &str) -> Option<&'static (dyn interface::DeviceDriver + Sync)> {
+        self.inner
+            .read(|inner| inner.find(compatible).map(|x| x.descriptor.device_driver))
+    }
+
+    /// Print all registered drivers in init order, together with their state.
+    pub fn enumerate_drivers(&self) {
+        let mut i: usize = 1;
+        self.for_each_driver(|driver, state| {
+            info!("      {}. {}: {}", i, driver.compatible(), state);
+            i += 1;
         });
     }
 }
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Ordering of drivers by their dependencies.
+//!
+//! Items are only known by their index, their name and the names they depend on, so this module
+//! does not depend on the rest of the kernel. Its tests run on the host, using the `host_tests`
+//! package.
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Why no order could be found. Holds the index of the offending item.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The item depends on a name that no item has.
+    UnknownDependency(usize),
+
+    /// The item is part of a dependency cycle, or waits on one.
+    DependencyCycle(usize),
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return the indices of `num_items` items in an order where every item comes after its
+/// dependencies.
+///
+/// `name` and `dependencies` return the name of an item and the names it depends on. Among the
+/// items whose dependencies are satisfied, the original order is kept. Only the first `num_items`
+/// entries of the result are meaningful, and `num_items` must not exceed `N`.
+pub fn sort_by_dependencies<'a, const N: usize>(
+    num_items: usize,
+    name: impl Fn(usize) -> &'a str,
+    dependencies: impl Fn(usize) -> &'a [&'a str],
+) -> Result<[usize; N], Error> {
+    assert!(num_items <= N);
+
+    let find = |wanted: &str| (0..num_items).find(|&i| name(i) == wanted);
+
+    for i in 0..num_items {
+        if !dependencies(i).iter().all(|dep| find(dep).is_some()) {
+            return Err(Error::UnknownDependency(i));
+        }
+    }
+
+    let mut order = [0; N];
+    let mut placed = [false; N];
+
+    for next in order.iter_mut().take(num_items) {
+        // Pick the first unplaced item whose dependencies are all placed already.
+        let candidate = (0..num_items).find(|&i| {
+            !placed[i]
+                && dependencies(i)
+                    .iter()
+                    .all(|dep| matches!(find(dep), Some(x) if placed[x]))
+        });
+
+        let Some(i) = candidate else {
+            // Every remaining item waits on another remaining one.
+            let stuck = (0..num_items).find(|&i| !placed[i]).unwrap_or(0);
+
+            return Err(Error::DependencyCycle(stuck));
+        };
+
+        placed[i] = true;
+        *next = i;
+    }
+
+    Ok(order)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Item = (&'static str, &'static [&'static str]);
+
+    fn sort(items: &[Item]) -> Result<Vec<&'static str>, Error> {
+        let order: [usize; 8] = sort_by_dependencies(items.len(), |i| items[i].0, |i| items[i].1)?;
+
+        Ok(order[..items.len()].iter().map(|&i| items[i].0).collect())
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        let items: [Item; 3] = [("gpio", &["uart"]), ("timer", &[]), ("uart", &["timer"])];
+
+        assert_eq!(sort(&items), Ok(vec!["timer", "uart", "gpio"]));
+    }
+
+    #[test]
+    fn order_is_stable() {
+        let independent: [Item; 3] = [("c", &[]), ("a", &[]), ("b", &[])];
+        assert_eq!(sort(&independent), Ok(vec!["c", "a", "b"]));
+
+        // `a` and `c` only become ready after `b`, and keep their relative order.
+        let items: [Item; 4] = [("a", &["b"]), ("b", &[]), ("c", &["b"]), ("d", &[])];
+        assert_eq!(sort(&items), Ok(vec!["b", "a", "c", "d"]));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(sort(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn unknown_dependency() {
+        let items: [Item; 2] = [("a", &[]), ("b", &["a", "missing"])];
+
+        assert_eq!(sort(&items), Err(Error::UnknownDependency(1)));
+    }
+
+    #[test]
+    fn self_dependency() {
+        let items: [Item; 2] = [("a", &[]), ("b", &["b"])];
+
+        assert_eq!(sort(&items), Err(Error::DependencyCycle(1)));
+    }
+
+    #[test]
+    fn cycle() {
+        let items: [Item; 4] = [("a", &[]), ("b", &["d"]), ("c", &["b"]), ("d", &["c"])];
+
+        assert_eq!(sort(&items), Err(Error::DependencyCycle(1)));
+    }
+}
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2020-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Asynchronous exception handling.
+
+#[cfg(target_arch = "aarch64")]
+#[path = "../_arch/aarch64/exception/asynchronous.rs"]
+mod arch_asynchronous;
+
+use crate::{
+    bsp, debug,
+    synchronization::{interface::ReadWriteEx, InitStateLock},
+};
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Architectural Public Reexports
+//--------------------------------------------------------------------------------------------------
+pub use arch_asynchronous::{
+    is_local_irq_masked, local_irq_mask, local_irq_mask_save, local_irq_restore, local_irq_unmask,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Interrupt number as defined by the BSP.
+pub type IRQNumber = bsp::exception::asynchronous::IRQNumber;
+
+/// Interrupt descriptor.
+#[derive(Copy, Clone)]
+pub struct IRQHandlerDescriptor<T>
+where
+    T: Copy,
+{
+    /// The IRQ number.
+    number: T,
+
+    /// Descriptive name.
+    name: &'static str,
+
+    /// Reference to handler trait object.
+    handler: &'static (dyn interface::IRQHandler + Sync),
+}
+
+/// IRQ management interfaces.
+pub mod interface {
+    use super::Error;
+
+    /// Implemented by types that handle IRQs.
+    pub trait IRQHandler {
+        /// Called when the corresponding interrupt is asserted.
+        ///
+        /// Runs with IRQs masked. It must be short, and it must not block.
+        fn handle(&self);
+    }
+
+    /// IRQ management functions.
+    ///
+    /// The `BSP` is supposed to supply one global instance. Typically implemented by the
+    /// platform's interrupt controller.
+    pub trait IRQManager {
+        /// The IRQ number type depends on the implementation.
+        type IRQNumberType: Copy;
+
+        /// Register a handler.
+        fn register_handler(
+            &self,
+            irq_handler_descriptor: super::IRQHandlerDescriptor<Self::IRQNumberType>,
+        ) -> Result<(), Error>;
+
+        /// Enable an interrupt in the controller.
+        fn enable(&self, irq_number: &Self::IRQNumberType);
+
+        /// Handle pending interrupts.
+        ///
+        /// This function is called directly from the CPU's IRQ exception vector. On AArch64,
+        /// this means that the respective CPU core has disabled exception handling.
+        fn handle_pending_irqs(&self);
+    }
+}
+
+/// Errors of IRQ management.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The BSP did not register an IRQ manager, e.g. because the interrupt controller driver is
+    /// not up.
+    NoIRQManager,
+
+    /// A handler for the IRQ number is registered already.
+    AlreadyRegistered,
+}
+
+/// The type of the IRQ manager registered by the BSP.
+pub type KernelIRQManager = dyn interface::IRQManager<IRQNumberType = IRQNumber> + Sync;
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static CUR_IRQ_MANAGER: InitStateLock<Option<&'static KernelIRQManager>> = InitStateLock::new(None);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoIRQManager => write!(f, "No IRQ manager registered"),
+            Self::AlreadyRegistered => write!(f, "IRQ handler already registered"),
+        }
+    }
+}
+
+impl<T> IRQHandlerDescriptor<T>
+where
+    T: Copy,
+{
+    /// Create an instance.
+    pub const fn new(
+        number: T,
+        name: &'static str,
+        handler: &'static (dyn interface::IRQHandler + Sync),
+    ) -> Self {
+        Self {
+            number,
+            name,
+            handler,
+        }
+    }
+
+    /// Return the number.
+    pub const fn number(&self) -> T {
+        self.number
+    }
+
+    /// Return the name.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Return the handler.
+    pub const fn handler(&self) -> &'static (dyn interface::IRQHandler + Sync) {
+        self.handler
+    }
+}
+
+/// Executes the provided closure while IRQs and FIQs are masked on the executing core.
+///
+/// While the function temporarily changes the HW state of the executing core, it restores it to the
+/// previous state before returning, so this is deemed safe.
+#[inline(always)]
+pub fn exec_with_irq_masked<T>(f: impl FnOnce() -> T) -> T {
+    let saved = local_irq_mask_save();
+    let ret = f();
+    local_irq_restore(saved);
+
+    ret
+}
+
+/// Register a new IRQ manager.
+///
+/// Only possible during kernel init.
+pub fn register_irq_manager(new_manager: &'static KernelIRQManager) {
+    CUR_IRQ_MANAGER.write(|manager| *manager = Some(new_manager));
+}
+
+/// Register `descriptor`'s handler with the IRQ manager, and enable its interrupt.
+///
+/// Only possible during kernel init.
+pub fn register_irq_handler(descriptor: IRQHandlerDescriptor<IRQNumber>) -> Result<(), Error> {
+    let manager = CUR_IRQ_MANAGER
+        .read(|manager| *manager)
+        .ok_or(Error::NoIRQManager)?;
+
+    manager.register_handler(descriptor)?;
+    manager.enable(&descriptor.number());
+    debug!("Enabled IRQ: {}", descriptor.name());
+
+    Ok(())
+}
+
+/// Dispatch the pending IRQs to their handlers.
+///
+/// Called from the CPU's IRQ exception vector.
+pub fn handle_pending_irqs() {
+    match CUR_IRQ_MANAGER.read(|manager| *manager) {
+        Some(manager) => manager.handle_pending_irqs(),
+        None => panic!("IRQ taken without an IRQ manager"),
+    }
+}
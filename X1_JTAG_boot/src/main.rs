@@ -105,6 +105,9 @@
 //! 1. The kernel's entry point is the function `cpu::boot::arch_boot::_start()`.
 //!     - It is implemented in `src/_arch/__arch_name__/cpu/boot.s`.
 //! 2. Once finished with architectural setup, the arch code calls `kernel_init()`.
+//!     - The kernel is in `state::State::Init` while it runs.
+//! 3. At the end of `kernel_init()`, the kernel transitions to `state::State::SingleCoreMain`,
+//!    unmasks IRQs and jumps to `kernel_main()`.
 
 #![allow(clippy::upper_case_acronyms)]
 #![feature(asm_const)]
@@ -113,7 +116,6 @@
 #![feature(nonzero_min_max)]
 #![feature(panic_info_message)]
 #![feature(trait_alias)]
-#![feature(unchecked_math)]
 #![no_main]
 #![no_std]
 
@@ -121,8 +123,12 @@ mod bsp;
 mod console;
 mod cpu;
 mod driver;
+mod exception;
+#[cfg(feature = "monitor")]
+mod monitor;
 mod panic_wait;
 mod print;
+mod state;
 mod synchronization;
 mod time;
 
@@ -133,22 +139,81 @@ mod time;
 /// - Only a single core must be active and running this function.
 /// - The init calls in this function must appear in the correct order.
 unsafe fn kernel_init() -> ! {
+    // Install the exception vectors first, so that faults during init are reported, not hung on.
+    exception::handling_init();
+
+    // Initialize the timer next, so that all timestamps are valid. There is no console yet, so a
+    // failure is only reported once the drivers are up. Until then, delays and timeouts run
+    // long, but never short.
+    let timer_init_result = time::time_manager().init();
+
     // Initialize the BSP driver subsystem.
     if let Err(x) = bsp::driver::init() {
         panic!("Error initializing BSP driver subsystem: {}", x);
     }
 
     // Initialize all device drivers.
-    driver::driver_manager().init_drivers();
+    if let Err(x) = driver::driver_manager().init_drivers() {
+        panic!("Error initializing driver: {}", x);
+    }
     // println! is usable from here on.
 
+    if let Err(x) = timer_init_result {
+        panic!("Error initializing timer: {}", x);
+    }
+
+    // Timeouts are handled in the timer interrupt.
+    if let Err(x) = time::time_manager().init_irq() {
+        panic!("Error registering timer IRQ handler: {}", x);
+    }
+
+    // Globals guarded by an `InitStateLock` are read-only from here on.
+    state::state_manager().transition_to(state::State::SingleCoreMain);
+
+    // Only now, handlers can rely on the state of the kernel.
+    exception::asynchronous::local_irq_unmask();
+
     // Transition from unsafe to safe.
     kernel_main()
 }
 
 /// The main function running after the early init.
 fn kernel_main() -> ! {
+    // Color the output if a terminal answers. The binary log encoding is not meant for one.
+    #[cfg(not(feature = "binary_log"))]
+    if let Some(terminal) = console::primary_console() {
+        console::ansi::detect(terminal, console::ansi::BOOT_REPLY_TIMEOUT);
+    }
+
+    info!("Drivers:");
+    driver::driver_manager().enumerate_drivers();
+
+    let mut num_unavailable = 0;
+    driver::driver_manager().for_each_driver(|_, state| {
+        if state != driver::DriverState::Up {
+            num_unavailable += 1;
+        }
+    });
+    if num_unavailable > 0 {
+        warn!(
+            "Running in degraded mode: {} driver(s) not available",
+            num_unavailable
+        );
+    }
+
+    console::print_stats();
+
+    #[cfg(feature = "monitor")]
+    monitor::run();
+
     info!("Parking CPU core. Please connect over JTAG now.");
 
+    // Leave the hardware in a clean state for whatever gets loaded over JTAG. No interrupt must
+    // hit the drivers while they go down.
+    exception::asynchronous::local_irq_mask();
+    if let Err(x) = unsafe { bsp::driver::shutdown() } {
+        error!("Error shutting down driver: {}", x);
+    }
+
     cpu::wait_forever()
 }
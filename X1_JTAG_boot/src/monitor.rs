@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Interactive monitor.
+//!
+//! A minimal command prompt on the system console, run before the core is parked. Only compiled
+//! with the `monitor` feature.
+
+use crate::{console, driver, info, print, println, time};
+use core::time::Duration;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_LINE_LEN: usize = 64;
+
+/// The supported commands, together with their help text.
+const COMMANDS: &[(&str, &str)] = &[
+    ("help", "Show this list"),
+    ("term", "Detect an ANSI terminal and enable colors"),
+    ("drivers", "List the drivers and their state"),
+    ("stats", "Show the console statistics"),
+    ("uptime", "Show the time since power-on"),
+    ("timer", "Log a message in one second"),
+    ("heartbeat", "Log the uptime every ten seconds"),
+    ("cancel", "Cancel all timers"),
+    (
+        "loglevel",
+        "Set the log level: error, warn, info, debug or trace",
+    ),
+    ("park", "Leave the monitor and park the CPU core"),
+];
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+fn timer_expired() {
+    info!("Timer expired");
+}
+
+fn heartbeat() {
+    let uptime = time::time_manager().uptime();
+    info!(
+        "Heartbeat at {}.{:06} s",
+        uptime.as_secs(),
+        uptime.subsec_micros()
+    );
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Read and execute commands until `park` is entered.
+pub fn run() {
+    let mut buf = [0; MAX_LINE_LEN];
+
+    println!("Monitor ready. Type `help` for a list of commands.");
+
+    loop {
+        print!("> ");
+
+        // Ctrl-C only discards the line.
+        let Ok(line) = console::read_line(console::console(), &mut buf) else {
+            continue;
+        };
+
+        // Commands take at most one argument, separated by spaces.
+        let line = line.trim();
+        let (command, argument) = match line.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            "" => (),
+            "help" => {
+                for (command, help) in COMMANDS {
+                    println!("  {:<10} {}", command, help);
+                }
+            }
+            "term" => match console::primary_console()
+                .and_then(|terminal| console::ansi::detect(terminal, console::ansi::REPLY_TIMEOUT))
+            {
+                Some(size) => println!("ANSI terminal, {}x{}", size.columns, size.rows),
+                None => println!("No ANSI terminal detected"),
+            },
+            "drivers" => driver::driver_manager().enumerate_drivers(),
+            "stats" => console::print_stats(),
+            "uptime" => {
+                let uptime = time::time_manager().uptime();
+                println!("{}.{:06} s", uptime.as_secs(), uptime.subsec_micros());
+            }
+            "timer" => {
+                if let Err(x) =
+                    time::time_manager().set_timeout_once(Duration::from_secs(1), timer_expired)
+                {
+                    println!("Error: {}", x);
+                }
+            }
+            "heartbeat" => {
+                if let Err(x) =
+                    time::time_manager().set_timeout_periodic(Duration::from_secs(10), heartbeat)
+                {
+                    println!("Error: {}", x);
+                }
+            }
+            "cancel" => time::time_manager().cancel_timeouts(),
+            "loglevel" => match argument.parse() {
+                Ok(level) => print::set_log_level(level),
+                Err(()) => println!("Usage: loglevel error|warn|info|debug|trace"),
+            },
+            #[cfg(not(feature = "binary_log"))]
+            "logloc" => match argument {
+                "on" => print::set_log_location(true),
+                "off" => print::set_log_location(false),
+                _ => println!("Usage: logloc on|off"),
+            },
+            "park" => return,
+            x => println!("Unknown command: {}", x),
+        }
+    }
+}
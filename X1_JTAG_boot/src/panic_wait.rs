@@ -4,7 +4,10 @@
 
 //! A panic handler that infinitely waits.
 
-use crate::{cpu, println};
+use crate::{
+    console::ansi::{Color, Colored},
+    cpu, println, synchronization,
+};
 use core::panic::PanicInfo;
 
 //--------------------------------------------------------------------------------------------------
@@ -45,6 +48,10 @@ fn panic(info: &PanicInfo) -> ! {
     // Protect against panic infinite loops if any of the following code panics itself.
     panic_prevent_reenter();
 
+    // The panic might have hit while the console or UART lock was held. Only this core runs, so
+    // ignore the locks instead of deadlocking on the panic message.
+    unsafe { synchronization::bypass_locks() };
+
     let timestamp = crate::time::time_manager().uptime();
     let (location, line, column) = match info.location() {
         Some(loc) => (loc.file(), loc.line(), loc.column()),
@@ -52,11 +59,12 @@ fn panic(info: &PanicInfo) -> ! {
     };
 
     println!(
-        "[  {:>3}.{:06}] Kernel panic!\n\n\
+        "[  {:>3}.{:06}] {}\n\n\
         Panic location:\n      File '{}', line {}, column {}\n\n\
         {}",
         timestamp.as_secs(),
         timestamp.subsec_micros(),
+        Colored::new(Color::Red, "Kernel panic!"),
         location,
         line,
         column,
@@ -3,19 +3,208 @@
 // Copyright (c) 2018-2023 Andre Richter <andre.o.richter@gmail.com>
 
 //! Printing.
+//!
+//! The logging macros `error!`, `warn!`, `info!`, `debug!` and `trace!` filter by level twice:
+//!
+//! - At compile time, against [`STATIC_MAX_LEVEL`]. It is selected with one of the
+//!   `max_log_level_*` cargo features and defaults to [`LogLevel::Trace`]. Disabled levels are
+//!   compiled out.
+//! - At runtime, against the level set with [`set_log_level()`], which defaults to
+//!   [`LogLevel::Info`].
+//!
+//! With [`set_log_location()`], every log line is additionally tagged with the module path and
+//! the source location it was logged from.
+//!
+//! With the `binary_log` feature, log messages are sent in a compact binary encoding instead, see
+//! [`binary`].
+
+#[cfg(feature = "binary_log")]
+pub mod binary;
 
 use crate::console;
-use core::fmt;
+use core::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+#[cfg(not(feature = "binary_log"))]
+use crate::{
+    console::ansi::{Color, Colored},
+    time,
+};
+#[cfg(not(feature = "binary_log"))]
+use core::{sync::atomic::AtomicBool, time::Duration};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+#[cfg(not(feature = "binary_log"))]
+static LOG_LOCATION: AtomicBool = AtomicBool::new(false);
+
+/// The `[L sss.uuuuuu]` prefix of a log line.
+#[cfg(not(feature = "binary_log"))]
+struct LogPrefix {
+    level: LogLevel,
+    timestamp: Duration,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Log levels, from the most to the least severe.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The most verbose level that is compiled in.
+#[cfg(feature = "max_log_level_error")]
+pub const STATIC_MAX_LEVEL: LogLevel = LogLevel::Error;
+
+/// The most verbose level that is compiled in.
+#[cfg(all(feature = "max_log_level_warn", not(feature = "max_log_level_error")))]
+pub const STATIC_MAX_LEVEL: LogLevel = LogLevel::Warn;
+
+/// The most verbose level that is compiled in.
+#[cfg(all(
+    feature = "max_log_level_info",
+    not(any(feature = "max_log_level_error", feature = "max_log_level_warn"))
+))]
+pub const STATIC_MAX_LEVEL: LogLevel = LogLevel::Info;
+
+/// The most verbose level that is compiled in.
+#[cfg(all(
+    feature = "max_log_level_debug",
+    not(any(
+        feature = "max_log_level_error",
+        feature = "max_log_level_warn",
+        feature = "max_log_level_info"
+    ))
+))]
+pub const STATIC_MAX_LEVEL: LogLevel = LogLevel::Debug;
+
+/// The most verbose level that is compiled in.
+#[cfg(not(any(
+    feature = "max_log_level_error",
+    feature = "max_log_level_warn",
+    feature = "max_log_level_info",
+    feature = "max_log_level_debug"
+)))]
+pub const STATIC_MAX_LEVEL: LogLevel = LogLevel::Trace;
 
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+/// Set the most verbose level that is printed.
+///
+/// Levels above [`STATIC_MAX_LEVEL`] stay disabled regardless.
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Return whether messages of the given level are printed.
+#[inline(always)]
+pub fn log_enabled(level: LogLevel) -> bool {
+    // The first comparison is constant, so disabled levels are optimized out entirely.
+    (level as u8) <= (STATIC_MAX_LEVEL as u8) && (level as u8) <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Parses the lowercase level names, e.g. `warn`.
+impl FromStr for LogLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(not(feature = "binary_log"))]
+impl LogLevel {
+    /// The marker that is printed in front of the timestamp.
+    fn marker(self) -> char {
+        match self {
+            LogLevel::Error => 'E',
+            LogLevel::Warn => 'W',
+            LogLevel::Info => ' ',
+            LogLevel::Debug => 'D',
+            LogLevel::Trace => 'T',
+        }
+    }
+
+    /// The color of the prefix on ANSI terminals.
+    fn color(self) -> Color {
+        match self {
+            LogLevel::Error => Color::Red,
+            LogLevel::Warn => Color::Yellow,
+            LogLevel::Info => Color::Default,
+            LogLevel::Debug => Color::Blue,
+            LogLevel::Trace => Color::Gray,
+        }
+    }
+}
+
+#[cfg(not(feature = "binary_log"))]
+impl fmt::Display for LogPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{} {:>3}.{:06}]",
+            self.level.marker(),
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros()
+        )
+    }
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     console::console().write_fmt(args).unwrap();
 }
 
+/// Tag log lines with module path and source location, or stop doing so.
+#[cfg(not(feature = "binary_log"))]
+pub fn set_log_location(enable: bool) {
+    LOG_LOCATION.store(enable, Ordering::Relaxed);
+}
+
+#[cfg(not(feature = "binary_log"))]
+#[doc(hidden)]
+pub fn _print_log(level: LogLevel, module_path: &str, file: &str, line: u32, args: fmt::Arguments) {
+    let timestamp = time::time_manager().uptime();
+    let prefix = Colored::new(level.color(), LogPrefix { level, timestamp });
+
+    if LOG_LOCATION.load(Ordering::Relaxed) {
+        _print(format_args_nl!(
+            "{} {} ({}:{}): {}",
+            prefix,
+            module_path,
+            file,
+            line,
+            args
+        ));
+    } else {
+        _print(format_args_nl!("{} {}", prefix, args));
+    }
+}
+
 /// Prints without a newline.
 ///
 /// Carbon copy from <https://doc.rust-lang.org/src/std/macros.rs.html>
@@ -35,50 +224,78 @@ macro_rules! println {
     })
 }
 
-/// Prints an info, with a newline.
+#[cfg(not(feature = "binary_log"))]
+#[doc(hidden)]
 #[macro_export]
-macro_rules! info {
-    ($string:expr) => ({
-        let timestamp = $crate::time::time_manager().uptime();
+macro_rules! _log {
+    ($level:ident, $($arg:tt)*) => ({
+        if $crate::print::log_enabled($crate::print::LogLevel::$level) {
+            $crate::print::_print_log(
+                $crate::print::LogLevel::$level,
+                module_path!(),
+                file!(),
+                line!(),
+                format_args!($($arg)*),
+            );
+        }
+    })
+}
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[  {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[  {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
+#[cfg(feature = "binary_log")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _log {
+    ($level:ident, $format_string:literal $(, $arg:expr)* $(,)?) => ({
+        if $crate::print::log_enabled($crate::print::LogLevel::$level) {
+            // Let the compiler check the arguments against the format string. Never executed.
+            if false {
+                let _ = format_args!($format_string $(, $arg)*);
+            }
+
+            const FORMAT_STRING: &str = concat!($format_string, "\0");
+            const _: () = $crate::print::binary::check_format_string(FORMAT_STRING);
+            #[link_section = ".log_strings"]
+            static INTERNED: [u8; FORMAT_STRING.len()] =
+                $crate::print::binary::intern(FORMAT_STRING);
+
+            #[allow(unused_imports)]
+            use $crate::print::binary::{EncodeDisplay as _, EncodeRaw as _};
+
+            #[allow(unused_mut)]
+            let mut encoder =
+                $crate::print::binary::Encoder::start($crate::print::LogLevel::$level, &INTERNED);
+            $((&$crate::print::binary::Arg(&$arg)).encode(&mut encoder);)*
+            encoder.finish();
+        }
     })
 }
 
+/// Prints an error, with a newline.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::_log!(Error, $($arg)*));
+}
+
 /// Prints a warning, with a newline.
 #[macro_export]
 macro_rules! warn {
-    ($string:expr) => ({
-        let timestamp = $crate::time::time_manager().uptime();
+    ($($arg:tt)*) => ($crate::_log!(Warn, $($arg)*));
+}
 
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-        ));
-    });
-    ($format_string:expr, $($arg:tt)*) => ({
-        let timestamp = $crate::time::time_manager().uptime();
-
-        $crate::print::_print(format_args_nl!(
-            concat!("[W {:>3}.{:06}] ", $format_string),
-            timestamp.as_secs(),
-            timestamp.subsec_micros(),
-            $($arg)*
-        ));
-    })
+/// Prints an info, with a newline.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::_log!(Info, $($arg)*));
+}
+
+/// Prints a debug message, with a newline.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::_log!(Debug, $($arg)*));
+}
+
+/// Prints a trace message, with a newline.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::_log!(Trace, $($arg)*));
 }
@@ -0,0 +1,169 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Binary log encoding.
+//!
+//! With the `binary_log` feature, the logging macros do not format their message. Instead, the
+//! format string is interned into the `.log_strings` section of the kernel ELF, which is not
+//! loaded, and only its address is sent, followed by the raw arguments. The `log_decoder` host
+//! tool reads the format strings back from the ELF and reconstructs the messages.
+//!
+//! # Wire format
+//!
+//! Each message is a frame of at most 256 bytes, which is written to the console in one piece:
+//!
+//! | Field       | Encoding                                          |
+//! |-------------|---------------------------------------------------|
+//! | start       | `0xfe`, which never occurs in UTF-8 text          |
+//! | length      | `u8`, the number of bytes that follow             |
+//! | format ID   | `u32`, little endian, address in `.log_strings`   |
+//! | timestamp   | `u64`, little endian, microseconds of uptime      |
+//! | level       | `u8`, the [`LogLevel`] value                      |
+//! | arguments   | tag byte and payload each, see below              |
+//! | end         | tag `0`                                           |
+//!
+//! Arguments:
+//!
+//! - Tag `1`, unsigned integers: unsigned LEB128.
+//! - Tag `2`, signed integers: the size of the type in bytes, then the value zigzag encoded and as
+//!   unsigned LEB128.
+//! - Tag `3`, `char`: unsigned LEB128 of the code point.
+//! - Tag `4`, `&str` and everything else that implements `Display`: UTF-8, terminated by `0`.
+//!   `Display` types are formatted on the kernel side.
+//! - Tag `5`, `bool`: `0` or `1`.
+//! - Tag `6`, right before the end: the remaining arguments did not fit into the frame. The last
+//!   string might be cut short as well.
+//!
+//! The length lets the decoder check a frame before it prints it. If the check fails, for example
+//! because the start of the log buffer was overwritten in the middle of a frame, the decoder
+//! resumes its search for the next start byte right after the bad one.
+//!
+//! Format strings are checked at compile time, see [`check_format_string()`].
+//!
+//! Everything outside of frames, for example panic messages, is plain text.
+
+mod frame;
+
+use super::LogLevel;
+use crate::{console, time};
+use core::fmt;
+
+pub use frame::{check_format_string, FRAME_START};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Encodes a single frame.
+pub struct Encoder {
+    frame: frame::Frame,
+}
+
+/// Wrapper that selects the encoding of a log argument.
+///
+/// Arguments of the types that are sent raw are encoded through [`EncodeRaw`], all others through
+/// [`EncodeDisplay`]. Which one is used is decided by method resolution on `&Arg<..>`: `EncodeRaw`
+/// is implemented for `Arg<..>`, so it is found before `EncodeDisplay`, which is implemented for
+/// `&Arg<..>` and only found after an auto-ref.
+pub struct Arg<'a, T: ?Sized>(pub &'a T);
+
+/// Encoding of log arguments that are sent raw.
+pub trait EncodeRaw {
+    /// Encode the argument.
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+/// Encoding of all other log arguments, which are formatted on the kernel side.
+pub trait EncodeDisplay {
+    /// Encode the argument.
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Copy a format string into an array, so that it can be placed into the `.log_strings` section.
+pub const fn intern<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut array = [0; N];
+
+    let mut i = 0;
+    while i < N {
+        array[i] = bytes[i];
+        i += 1;
+    }
+
+    array
+}
+
+impl Encoder {
+    /// Start a frame.
+    pub fn start(level: LogLevel, format_string: &'static [u8]) -> Self {
+        let timestamp = time::time_manager().uptime().as_micros() as u64;
+        let format_id = format_string.as_ptr() as usize as u32;
+
+        Self {
+            frame: frame::Frame::new(format_id, timestamp, level as u8),
+        }
+    }
+
+    /// Encode an unsigned integer argument.
+    pub fn unsigned(&mut self, value: u64) {
+        self.frame.unsigned(value);
+    }
+
+    /// Encode a signed integer argument of a type that is `size` bytes wide.
+    pub fn signed(&mut self, value: i64, size: u8) {
+        self.frame.signed(value, size);
+    }
+
+    /// Encode a `bool` argument.
+    pub fn bool(&mut self, value: bool) {
+        self.frame.bool(value);
+    }
+
+    /// Encode a character argument.
+    pub fn char(&mut self, value: char) {
+        self.frame.char(value);
+    }
+
+    /// Encode an argument that is formatted with `Display`.
+    pub fn display(&mut self, value: &(impl fmt::Display + ?Sized)) {
+        self.frame.display(value);
+    }
+
+    /// Finish the frame and write it to the console.
+    ///
+    /// The frame goes out in a single write, so frames from different cores are not interleaved.
+    pub fn finish(mut self) {
+        console::console().write_bytes(self.frame.finish());
+    }
+}
+
+macro_rules! encode_raw {
+    ($($t:ty),*: |$encoder:ident, $value:ident| $encode:expr) => {
+        $(
+            impl EncodeRaw for Arg<'_, $t> {
+                fn encode(&self, $encoder: &mut Encoder) {
+                    let $value = *self.0;
+                    $encode
+                }
+            }
+        )*
+    };
+}
+
+encode_raw!(u8, u16, u32, u64, usize: |encoder, value| encoder.unsigned(value as u64));
+encode_raw!(i8, i16, i32, i64, isize: |encoder, value| {
+    encoder.signed(value as i64, core::mem::size_of_val(&value) as u8)
+});
+encode_raw!(bool: |encoder, value| encoder.bool(value));
+encode_raw!(char: |encoder, value| encoder.char(value));
+
+impl<T: fmt::Display + ?Sized> EncodeDisplay for &Arg<'_, T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.display(self.0);
+    }
+}
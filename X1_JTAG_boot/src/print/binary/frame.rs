@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Frames of the binary log encoding.
+//!
+//! Builds frames in memory and checks format strings. Nothing in here depends on the rest of the
+//! kernel, so the `log_decoder` host tool uses the same definitions of the wire format, and its
+//! tests decode frames that were built by this code.
+
+use core::fmt;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Marks the start of a frame. Never occurs in UTF-8 text.
+pub const FRAME_START: u8 = 0xfe;
+
+/// The maximum size of a frame, including the start byte and the length.
+pub const MAX_FRAME_SIZE: usize = 256;
+
+#[allow(missing_docs)]
+pub const TAG_END: u8 = 0;
+#[allow(missing_docs)]
+pub const TAG_UNSIGNED: u8 = 1;
+#[allow(missing_docs)]
+pub const TAG_SIGNED: u8 = 2;
+#[allow(missing_docs)]
+pub const TAG_CHAR: u8 = 3;
+#[allow(missing_docs)]
+pub const TAG_STR: u8 = 4;
+#[allow(missing_docs)]
+pub const TAG_BOOL: u8 = 5;
+#[allow(missing_docs)]
+pub const TAG_TRUNCATED: u8 = 6;
+
+/// A frame that is being built.
+pub struct Frame {
+    buf: [u8; MAX_FRAME_SIZE],
+    len: usize,
+    truncated: bool,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Private Code
+//--------------------------------------------------------------------------------------------------
+
+/// Space that is kept free for `TAG_TRUNCATED` and `TAG_END`.
+const TRAILER_SIZE: usize = 2;
+
+fn leb128(mut value: u64, buf: &mut [u8; 10]) -> &[u8] {
+    let mut len = 0;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+
+    &buf[..len]
+}
+
+/// The number of bytes of the UTF-8 character that starts with `byte`.
+const fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
+    }
+}
+
+const fn is_align(c: u8) -> bool {
+    matches!(c, b'<' | b'^' | b'>')
+}
+
+/// Check the part of a placeholder that follows the colon.
+const fn check_spec(spec: &[u8]) {
+    // The fill can be any character, so skip it before looking at the rest.
+    let mut i = 0;
+    if !spec.is_empty() {
+        let fill_len = utf8_len(spec[0]);
+        if spec.len() > fill_len && is_align(spec[fill_len]) {
+            i = fill_len + 1;
+        } else if is_align(spec[0]) {
+            i = 1;
+        }
+    }
+
+    while i < spec.len() {
+        match spec[i] {
+            b'+' | b'-' | b'#' | b'.' | b'0'..=b'9' | b'x' | b'X' | b'o' | b'b' => (),
+            b'?' => panic!("Binary log: {{:?}} is not supported, use {{}}"),
+            b'$' | b'*' => panic!("Binary log: width and precision arguments are not supported"),
+            _ => panic!("Binary log: unsupported format spec"),
+        }
+        i += 1;
+    }
+}
+
+impl Frame {
+    fn remaining(&self) -> usize {
+        MAX_FRAME_SIZE - TRAILER_SIZE - self.len
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf[self.len..(self.len + bytes.len())].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    /// Add an argument of `tag` and `payload`, unless it does not fit anymore.
+    ///
+    /// Once an argument was dropped, all following ones are dropped as well. Otherwise, the
+    /// decoder would match them with the wrong placeholders.
+    fn push_arg(&mut self, tag: u8, payload: &[u8]) {
+        if self.truncated || self.remaining() < 1 + payload.len() {
+            self.truncated = true;
+            return;
+        }
+
+        self.push(&[tag]);
+        self.push(payload);
+    }
+}
+
+impl fmt::Write for Frame {
+    /// Append to a string argument, cutting it at a character boundary if it does not fit.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+
+        // Keep one byte for the terminating zero.
+        let space = self.remaining() - 1;
+        let mut len = s.len().min(space);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.truncated = len < s.len();
+
+        // Zero terminates the string, so it must not be part of it.
+        for b in s[..len].bytes() {
+            self.push(&[if b == 0 { b' ' } else { b }]);
+        }
+
+        Ok(())
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Check at compile time that the log decoder supports `format_string`.
+///
+/// Placeholders can be positional, but not named. Of the format spec, fill, alignment, sign, `#`,
+/// zero padding, width and precision are supported, and the types `x`, `X`, `o` and `b`.
+pub const fn check_format_string(format_string: &str) {
+    let s = format_string.as_bytes();
+
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        if i + 1 < s.len() && s[i + 1] == b'{' {
+            i += 2;
+            continue;
+        }
+
+        // The argument, up to the colon or the end of the placeholder.
+        i += 1;
+        while i < s.len() && s[i] != b':' && s[i] != b'}' {
+            if !s[i].is_ascii_digit() {
+                panic!("Binary log: named arguments are not supported");
+            }
+            i += 1;
+        }
+        if i >= s.len() || s[i] == b'}' {
+            continue;
+        }
+
+        // The spec. A closing brace is not valid as fill, so it ends the placeholder.
+        let start = i + 1;
+        while i < s.len() && s[i] != b'}' {
+            i += 1;
+        }
+        let (_, spec) = s.split_at(start);
+        let (spec, _) = spec.split_at(i - start);
+        check_spec(spec);
+    }
+}
+
+impl Frame {
+    /// Start a frame.
+    pub fn new(format_id: u32, timestamp: u64, level: u8) -> Self {
+        let mut frame = Self {
+            buf: [0; MAX_FRAME_SIZE],
+            len: 0,
+            truncated: false,
+        };
+
+        // The length is filled in by finish().
+        frame.push(&[FRAME_START, 0]);
+        frame.push(&format_id.to_le_bytes());
+        frame.push(&timestamp.to_le_bytes());
+        frame.push(&[level]);
+
+        frame
+    }
+
+    /// Add an unsigned integer argument.
+    pub fn unsigned(&mut self, value: u64) {
+        self.push_arg(TAG_UNSIGNED, leb128(value, &mut [0; 10]));
+    }
+
+    /// Add a signed integer argument of a type that is `size` bytes wide.
+    ///
+    /// The size is needed to print negative values in hexadecimal, octal or binary.
+    pub fn signed(&mut self, value: i64, size: u8) {
+        let mut buf = [0; 10];
+        let zigzag = leb128(((value << 1) ^ (value >> 63)) as u64, &mut buf);
+
+        let mut payload = [0; 11];
+        payload[0] = size;
+        payload[1..=zigzag.len()].copy_from_slice(zigzag);
+
+        self.push_arg(TAG_SIGNED, &payload[..=zigzag.len()]);
+    }
+
+    /// Add a `bool` argument.
+    pub fn bool(&mut self, value: bool) {
+        self.push_arg(TAG_BOOL, &[value as u8]);
+    }
+
+    /// Add a `char` argument.
+    pub fn char(&mut self, value: char) {
+        self.push_arg(TAG_CHAR, leb128(value as u64, &mut [0; 10]));
+    }
+
+    /// Add a string argument with the output of `value`'s `Display` implementation.
+    pub fn display(&mut self, value: &(impl fmt::Display + ?Sized)) {
+        // The tag, at least one byte of the string, and the terminating zero.
+        if self.truncated || self.remaining() < 3 {
+            self.truncated = true;
+            return;
+        }
+
+        self.push(&[TAG_STR]);
+        // Writing to a frame never fails.
+        let _ = fmt::Write::write_fmt(self, format_args!("{}", value));
+        self.push(&[0]);
+    }
+
+    /// Finish the frame and return its bytes.
+    pub fn finish(&mut self) -> &[u8] {
+        if self.truncated {
+            self.push(&[TAG_TRUNCATED]);
+        }
+        self.push(&[TAG_END]);
+
+        // At most MAX_FRAME_SIZE - 2, so it fits.
+        self.buf[1] = (self.len - 2) as u8;
+
+        &self.buf[..self.len]
+    }
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2020-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! State information about the kernel itself.
+
+use crate::cpu;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Different stages in the kernel execution.
+///
+/// The stages are passed strictly in the order they are declared in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum State {
+    /// The kernel starts booting in this state.
+    Init,
+
+    /// The kernel transitions to this state when jumping to `kernel_main()` (at the end of
+    /// `kernel_init()`, after all init calls are done).
+    SingleCoreMain,
+
+    /// The kernel transitions to this state when it boots the secondary cores, aka switches
+    /// execution mode to symmetric multiprocessing (SMP).
+    MultiCoreMain,
+}
+
+/// Maintains the kernel state and state transitions.
+pub struct StateManager(AtomicU8);
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+static STATE_MANAGER: StateManager = StateManager::new();
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Return a reference to the global StateManager.
+pub fn state_manager() -> &'static StateManager {
+    &STATE_MANAGER
+}
+
+impl State {
+    /// The state that must directly precede this one, if any.
+    const fn predecessor(self) -> Option<Self> {
+        match self {
+            Self::Init => None,
+            Self::SingleCoreMain => Some(Self::Init),
+            Self::MultiCoreMain => Some(Self::SingleCoreMain),
+        }
+    }
+}
+
+impl StateManager {
+    const INIT: u8 = 0;
+    const SINGLE_CORE_MAIN: u8 = 1;
+    const MULTI_CORE_MAIN: u8 = 2;
+
+    /// Create a new instance.
+    pub const fn new() -> Self {
+        Self(AtomicU8::new(Self::INIT))
+    }
+
+    const fn encode(state: State) -> u8 {
+        match state {
+            State::Init => Self::INIT,
+            State::SingleCoreMain => Self::SINGLE_CORE_MAIN,
+            State::MultiCoreMain => Self::MULTI_CORE_MAIN,
+        }
+    }
+
+    fn decode(state: u8) -> State {
+        match state {
+            Self::INIT => State::Init,
+            Self::SINGLE_CORE_MAIN => State::SingleCoreMain,
+            Self::MULTI_CORE_MAIN => State::MultiCoreMain,
+            _ => panic!("Invalid KERNEL_STATE"),
+        }
+    }
+
+    /// Return the current state.
+    pub fn state(&self) -> State {
+        Self::decode(self.0.load(Ordering::Acquire))
+    }
+
+    /// Return if the kernel is init state.
+    pub fn is_init(&self) -> bool {
+        self.state() == State::Init
+    }
+
+    /// Advance the kernel to `next`.
+    ///
+    /// Panics if the kernel is not currently in the state directly preceding `next`.
+    pub fn transition_to(&self, next: State) {
+        let Some(expected) = next.predecessor() else {
+            panic!("Transition to {:?} is not possible", next);
+        };
+
+        // From here on, `SpinLock` takes its ticket lock, which needs exclusive accesses to work.
+        if next == State::MultiCoreMain {
+            assert!(
+                cpu::is_mmu_and_dcache_enabled(),
+                "Transition to {:?} requires the MMU and the data cache to be on",
+                next
+            );
+        }
+
+        // Only the boot core changes the state, so a plain load and store are sufficient. An atomic
+        // read-modify-write would need exclusive accesses, which do not work with the MMU off.
+        let current = self.state();
+        if current != expected {
+            panic!(
+                "Transition to {:?} requested while state is {:?} instead of {:?}",
+                next, current, expected
+            );
+        }
+
+        self.0.store(Self::encode(next), Ordering::Release);
+    }
+}
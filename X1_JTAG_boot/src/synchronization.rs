@@ -10,7 +10,14 @@
 //!   - <https://stackoverflow.com/questions/59428096/understanding-the-send-trait>
 //!   - <https://doc.rust-lang.org/std/cell/index.html>
 
-use core::cell::UnsafeCell;
+mod ticket_lock;
+
+use crate::{exception, state};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use ticket_lock::TicketLock;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
@@ -26,32 +33,121 @@ pub mod interface {
         type Data;
 
         /// Locks the mutex and grants the closure temporary mutable access to the wrapped data.
-        fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R;
+        ///
+        /// The reference handed to the closure cannot outlive the call, so it cannot escape the
+        /// critical section.
+        fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R;
+    }
+
+    /// A reader-writer exclusion type.
+    ///
+    /// The implementing object allows either a number of readers or at most one writer at any point
+    /// in time.
+    pub trait ReadWriteEx {
+        /// The type of encapsulated data.
+        type Data;
+
+        /// Grants temporary mutable access to the encapsulated data.
+        fn write<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R;
+
+        /// Grants temporary immutable access to the encapsulated data.
+        fn read<R>(&self, f: impl FnOnce(&Self::Data) -> R) -> R;
     }
 }
 
-/// A pseudo-lock for teaching purposes.
+/// A spinlock granting exclusive access to the wrapped data across all cores.
 ///
-/// In contrast to a real Mutex implementation, does not protect against concurrent access from
-/// other cores to the contained data. This part is preserved for later lessons.
+/// Contenders are served in the order they arrived, see [`TicketLock`]. The lock is not reentrant:
+/// taking it again while the same core holds it deadlocks. It does not mask interrupts either, so
+/// it must not be taken from an interrupt handler that might have interrupted the lock's owner on
+/// the same core.
 ///
-/// The lock will only be used as long as it is safe to do so, i.e. as long as the kernel is
-/// executing single-threaded, aka only running on a single core with interrupts disabled.
-pub struct NullLock<T>
+/// The ticket lock is only taken once the kernel is in [`state::State::MultiCoreMain`]. Its
+/// read-modify-write access compiles to exclusive loads and stores, which never succeed on the
+/// Raspberry Pi's cores while the MMU is off and all memory is treated as Device memory. Before
+/// that state, only the boot core runs, so there is nobody to exclude, and the lock hands out the
+/// data right away. Entering the state therefore requires the MMU and the data cache to be on.
+pub struct SpinLock<T>
 where
     T: ?Sized,
 {
+    lock: TicketLock,
     data: UnsafeCell<T>,
 }
 
+/// A [`SpinLock`] that additionally masks IRQs and FIQs on the executing core while it is held.
+///
+/// Use this for data that is shared between interrupt handlers and the code they might interrupt.
+/// The previous interrupt mask is saved before and restored after the critical section, so locking
+/// another `IRQSafeLock` inside the critical section is fine. Like [`SpinLock`], the lock itself is
+/// not reentrant.
+pub struct IRQSafeLock<T>
+where
+    T: ?Sized,
+{
+    inner: SpinLock<T>,
+}
+
+/// A lock for data that is written only during the kernel's init phase and read-only afterwards.
+///
+/// While the kernel is initializing, only a single core runs with interrupts masked, so writers
+/// need no exclusion. After the transition out of [`state::StateManager`]'s init state, readers
+/// can proceed without locking because nobody writes anymore. Late writes cause a panic.
+pub struct InitStateLock<T>
+where
+    T: ?Sized,
+{
+    data: UnsafeCell<T>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Global instances
+//--------------------------------------------------------------------------------------------------
+
+/// Set by [`bypass_locks()`].
+static LOCKS_BYPASSED: AtomicBool = AtomicBool::new(false);
+
 //--------------------------------------------------------------------------------------------------
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
-unsafe impl<T> Send for NullLock<T> where T: ?Sized + Send {}
-unsafe impl<T> Sync for NullLock<T> where T: ?Sized + Send {}
+/// Stop taking [`SpinLock`]s, so that a panic can still print if the panicking code held the
+/// console or UART lock.
+///
+/// # Safety
+///
+/// - Only for the panic path. No other core may run anymore.
+/// - Data behind a lock that was held when the panic hit may be inconsistent.
+pub unsafe fn bypass_locks() {
+    LOCKS_BYPASSED.store(true, Ordering::Relaxed);
+}
+
+unsafe impl<T> Send for SpinLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for SpinLock<T> where T: ?Sized + Send {}
 
-impl<T> NullLock<T> {
+impl<T> SpinLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            lock: TicketLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T> IRQSafeLock<T> {
+    /// Create an instance.
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: SpinLock::new(data),
+        }
+    }
+}
+
+unsafe impl<T> Send for InitStateLock<T> where T: ?Sized + Send {}
+unsafe impl<T> Sync for InitStateLock<T> where T: ?Sized + Send {}
+
+impl<T> InitStateLock<T> {
     /// Create an instance.
     pub const fn new(data: T) -> Self {
         Self {
@@ -64,14 +160,64 @@ impl<T> NullLock<T> {
 // OS Interface Code
 //------------------------------------------------------------------------------
 
-impl<T> interface::Mutex for NullLock<T> {
+impl<T> interface::Mutex for SpinLock<T> {
+    type Data = T;
+
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        // A plain load, so it is fine with the MMU off, just like the flag in `panic_wait`.
+        if LOCKS_BYPASSED.load(Ordering::Relaxed) {
+            return f(unsafe { &mut *self.data.get() });
+        }
+
+        // Plain loads as well. Only the boot core runs before the transition, see the type's
+        // documentation.
+        if state::state_manager().state() != state::State::MultiCoreMain {
+            return f(unsafe { &mut *self.data.get() });
+        }
+
+        self.lock.acquire();
+
+        // The ticket lock guarantees that this mutable reference is only given out once at a
+        // time.
+        let data = unsafe { &mut *self.data.get() };
+        let ret = f(data);
+
+        self.lock.release();
+        ret
+    }
+}
+
+impl<T> interface::Mutex for IRQSafeLock<T> {
+    type Data = T;
+
+    fn lock<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        // Mask before taking the spinlock, so that an interrupt handler on this core can never find
+        // the lock held by the code it interrupted.
+        exception::asynchronous::exec_with_irq_masked(|| self.inner.lock(f))
+    }
+}
+
+impl<T> interface::ReadWriteEx for InitStateLock<T> {
     type Data = T;
 
-    fn lock<'a, R>(&'a self, f: impl FnOnce(&'a mut Self::Data) -> R) -> R {
-        // In a real lock, there would be code encapsulating this line that ensures that this
-        // mutable reference will ever only be given out once at a time.
+    fn write<R>(&self, f: impl FnOnce(&mut Self::Data) -> R) -> R {
+        assert!(
+            state::state_manager().is_init(),
+            "InitStateLock::write called after kernel init phase"
+        );
+        assert!(
+            exception::asynchronous::is_local_irq_masked(),
+            "InitStateLock::write called with IRQs unmasked"
+        );
+
         let data = unsafe { &mut *self.data.get() };
 
         f(data)
     }
+
+    fn read<R>(&self, f: impl FnOnce(&Self::Data) -> R) -> R {
+        let data = unsafe { &*self.data.get() };
+
+        f(data)
+    }
 }
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2018-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Ticket lock.
+//!
+//! The raw locking algorithm behind [`SpinLock`](super::SpinLock). It only depends on `core`
+//! atomics, so it is architecture independent. Its tests run on the host, using the `host_tests`
+//! package.
+//!
+//! On `AArch64`, the atomic read-modify-write operations used here are lowered to exclusive
+//! load/store pairs (`LDAXR`/`STLXR`), or to single LSE instructions like `LDADDA` if the target
+//! supports them. Exclusives need Normal, cacheable memory: with the MMU off, the store part can
+//! fail forever on the Raspberry Pi's cores. This is why [`SpinLock`](super::SpinLock) only uses
+//! the lock once the MMU and the caches are on.
+//!
+//! # Resources
+//!
+//! - <https://en.wikipedia.org/wiki/Ticket_lock>
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A fair, first-come first-served lock without any associated data.
+///
+/// Every contender draws a ticket from `next_ticket` and spins until `now_serving` shows its
+/// number. Both counters wrap around, which is fine as long as fewer than `u32::MAX` contenders
+/// wait at the same time.
+pub struct TicketLock {
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl TicketLock {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            next_ticket: AtomicU32::new(0),
+            now_serving: AtomicU32::new(0),
+        }
+    }
+
+    /// Spin until the lock is acquired.
+    pub fn acquire(&self) {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+
+        // The Acquire load pairs with the Release store in `release()`, so that all writes of the
+        // previous owner are visible once our number is served.
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Release the lock and hand it to the next ticket in line.
+    ///
+    /// Must only be called by the current owner.
+    pub fn release(&self) {
+        // Only the owner ever writes `now_serving`, so a plain load/store pair is sufficient and
+        // saves an exclusive access.
+        let now_serving = self.now_serving.load(Ordering::Relaxed);
+        self.now_serving
+            .store(now_serving.wrapping_add(1), Ordering::Release);
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::UnsafeCell,
+        sync::{Arc, Mutex},
+        thread,
+        vec::Vec,
+    };
+
+    /// A counter that is only protected by the lock under test.
+    struct Shared {
+        lock: TicketLock,
+        counter: UnsafeCell<u64>,
+    }
+
+    unsafe impl Sync for Shared {}
+
+    #[test]
+    fn mutual_exclusion() {
+        const NUM_THREADS: u64 = 4;
+        const NUM_ITERATIONS: u64 = 10_000;
+
+        let shared = Arc::new(Shared {
+            lock: TicketLock::new(),
+            counter: UnsafeCell::new(0),
+        });
+
+        let threads: Vec<_> = (0..NUM_THREADS)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    for _ in 0..NUM_ITERATIONS {
+                        shared.lock.acquire();
+
+                        // A non-atomic read-modify-write, so that any overlap loses increments.
+                        let counter = unsafe { &mut *shared.counter.get() };
+                        *counter = core::hint::black_box(*counter) + 1;
+
+                        shared.lock.release();
+                    }
+                })
+            })
+            .collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(
+            unsafe { *shared.counter.get() },
+            NUM_THREADS * NUM_ITERATIONS
+        );
+    }
+
+    #[test]
+    fn first_come_first_served() {
+        const NUM_THREADS: u32 = 8;
+
+        let lock = Arc::new(TicketLock::new());
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        lock.acquire();
+
+        // Queue up the threads one after another, each only once the previous one drew its ticket.
+        let threads: Vec<_> = (0..NUM_THREADS)
+            .map(|i| {
+                let (thread_lock, order) = (Arc::clone(&lock), Arc::clone(&order));
+                let t = thread::spawn(move || {
+                    thread_lock.acquire();
+                    order.lock().unwrap().push(i);
+                    thread_lock.release();
+                });
+
+                while lock.next_ticket.load(Ordering::Relaxed) != i + 2 {
+                    thread::yield_now();
+                }
+
+                t
+            })
+            .collect();
+
+        lock.release();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), (0..NUM_THREADS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tickets_wrap_around() {
+        let lock = TicketLock {
+            next_ticket: AtomicU32::new(u32::MAX - 1),
+            now_serving: AtomicU32::new(u32::MAX - 1),
+        };
+
+        for _ in 0..4 {
+            lock.acquire();
+            lock.release();
+        }
+
+        assert_eq!(lock.next_ticket.load(Ordering::Relaxed), 2);
+        assert_eq!(lock.now_serving.load(Ordering::Relaxed), 2);
+    }
+}
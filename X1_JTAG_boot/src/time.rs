@@ -8,14 +8,52 @@
 #[path = "_arch/aarch64/time.rs"]
 mod arch_time;
 
-use core::time::Duration;
+mod counter;
+mod instant;
+mod timeout_queue;
+
+use crate::{
+    bsp, cpu, exception,
+    synchronization::{interface::Mutex, IRQSafeLock},
+    warn,
+};
+use core::{fmt, time::Duration};
+use timeout_queue::TimeoutQueue;
+
+pub use instant::Instant;
 
 //--------------------------------------------------------------------------------------------------
 // Public Definitions
 //--------------------------------------------------------------------------------------------------
 
 /// Provides time management functions.
-pub struct TimeManager;
+pub struct TimeManager {
+    timeouts: IRQSafeLock<TimeoutQueue<TimeoutCallback>>,
+}
+
+/// A function that is called when a timeout expires.
+///
+/// Callbacks run from the timer interrupt, with IRQs masked. They must be short and must not block.
+pub type TimeoutCallback = fn();
+
+/// Errors of the time subsystem.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// A bounded wait ran out of time.
+    Timeout,
+
+    /// The firmware did not set up the counter frequency.
+    FrequencyZero,
+
+    /// A timeout is too far in the future to be represented.
+    DurationTooLong,
+
+    /// A periodic timeout's period is shorter than the timer's resolution.
+    PeriodTooShort,
+
+    /// All timeout slots are taken.
+    TooManyTimeouts,
+}
 
 //--------------------------------------------------------------------------------------------------
 // Global instances
@@ -27,6 +65,18 @@ static TIME_MANAGER: TimeManager = TimeManager::new();
 // Public Code
 //--------------------------------------------------------------------------------------------------
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Timeout => write!(f, "Timed out"),
+            Error::FrequencyZero => write!(f, "Counter frequency is zero"),
+            Error::DurationTooLong => write!(f, "Duration too long"),
+            Error::PeriodTooShort => write!(f, "Period too short"),
+            Error::TooManyTimeouts => write!(f, "Too many timeouts"),
+        }
+    }
+}
+
 /// Return a reference to the global TimeManager.
 pub fn time_manager() -> &'static TimeManager {
     &TIME_MANAGER
@@ -35,7 +85,61 @@ pub fn time_manager() -> &'static TimeManager {
 impl TimeManager {
     /// Create an instance.
     pub const fn new() -> Self {
-        Self
+        Self {
+            timeouts: IRQSafeLock::new(TimeoutQueue::new()),
+        }
+    }
+
+    /// Program the timer for the earliest pending timeout, or stop it if there is none.
+    fn arm_timer(timeouts: &TimeoutQueue<TimeoutCallback>) {
+        match timeouts.next_due() {
+            Some(due) => arch_time::set_timer_deadline(due),
+            None => arch_time::disable_timer(),
+        }
+    }
+
+    fn add_timeout(
+        &self,
+        duration: Duration,
+        period: Option<u64>,
+        callback: TimeoutCallback,
+    ) -> Result<(), Error> {
+        let due = Instant::now()
+            .checked_add(duration)
+            .ok_or(Error::DurationTooLong)?;
+
+        self.timeouts.lock(|timeouts| {
+            timeouts
+                .add(due, period, callback)
+                .map_err(|_| Error::TooManyTimeouts)?;
+            Self::arm_timer(timeouts);
+
+            Ok(())
+        })
+    }
+
+    /// Initialize the timer.
+    ///
+    /// # Safety
+    ///
+    /// - Must only be called during kernel init, before any time is measured.
+    pub unsafe fn init(&self) -> Result<(), Error> {
+        arch_time::init()
+    }
+
+    /// Register the handler of the timer interrupt.
+    ///
+    /// # Safety
+    ///
+    /// - Must only be called during kernel init, after the interrupt controller driver is up.
+    pub unsafe fn init_irq(&'static self) -> Result<(), exception::asynchronous::Error> {
+        let descriptor = exception::asynchronous::IRQHandlerDescriptor::new(
+            bsp::exception::asynchronous::irq_map::ARM_NS_PHYSICAL_TIMER,
+            "ARM Generic Timer",
+            self,
+        );
+
+        exception::asynchronous::register_irq_handler(descriptor)
     }
 
     /// The uptime since power-on of the device.
@@ -45,9 +149,95 @@ impl TimeManager {
         arch_time::uptime()
     }
 
+    /// Call `callback` once, after `duration` has passed.
+    pub fn set_timeout_once(
+        &self,
+        duration: Duration,
+        callback: TimeoutCallback,
+    ) -> Result<(), Error> {
+        self.add_timeout(duration, None, callback)
+    }
+
+    /// Call `callback` every `period`, starting after the first `period` has passed.
+    ///
+    /// If the callback falls behind, missed calls are skipped.
+    pub fn set_timeout_periodic(
+        &self,
+        period: Duration,
+        callback: TimeoutCallback,
+    ) -> Result<(), Error> {
+        let ticks = arch_time::duration_to_ticks(period).ok_or(Error::DurationTooLong)?;
+
+        // A period that rounds down to zero counter ticks would never advance the deadline.
+        if ticks == 0 {
+            return Err(Error::PeriodTooShort);
+        }
+
+        self.add_timeout(period, Some(ticks), callback)
+    }
+
+    /// Drop all pending timeouts and stop the timer.
+    pub fn cancel_timeouts(&self) {
+        self.timeouts.lock(|timeouts| {
+            *timeouts = TimeoutQueue::new();
+            Self::arm_timer(timeouts);
+        });
+    }
+
     /// Spin for a given duration.
-    #[cfg(feature = "bsp_rpi3")]
     pub fn spin_for(&self, duration: Duration) {
-        arch_time::spin_for(duration)
+        match Instant::now().checked_add(duration) {
+            Some(deadline) => self.spin_until(deadline),
+            None => warn!("spin_for: {}. Skipping", Error::DurationTooLong),
+        }
+    }
+
+    /// Spin until `deadline` has passed.
+    pub fn spin_until(&self, deadline: Instant) {
+        while Instant::now() < deadline {
+            cpu::nop();
+        }
+    }
+
+    /// Spin while `condition` holds, but at most for `timeout`.
+    ///
+    /// Meant for polling hardware status bits. Returns [`Error::Timeout`] if `condition` still
+    /// holds after `timeout`.
+    pub fn spin_while(
+        &self,
+        mut condition: impl FnMut() -> bool,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        // A timeout too big to be represented means no timeout at all.
+        let deadline = Instant::now().checked_add(timeout);
+
+        while condition() {
+            if deadline.is_some_and(|x| Instant::now() >= x) {
+                return Err(Error::Timeout);
+            }
+
+            cpu::nop();
+        }
+
+        Ok(())
+    }
+}
+
+//------------------------------------------------------------------------------
+// OS Interface Code
+//------------------------------------------------------------------------------
+
+impl exception::asynchronous::interface::IRQHandler for TimeManager {
+    /// Run the callbacks of all expired timeouts and re-arm the timer.
+    fn handle(&self) {
+        let now = Instant::now();
+
+        // Callbacks run without the lock held, so that they can add new timeouts.
+        while let Some(callback) = self.timeouts.lock(|timeouts| timeouts.pop_expired(now)) {
+            callback();
+        }
+
+        // Moving the deadline, or stopping the timer, also deasserts the interrupt.
+        self.timeouts.lock(|timeouts| Self::arm_timer(timeouts));
     }
 }
@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2018-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Conversions between timer counter values and `Duration`.
+//!
+//! The math does not depend on the processor architecture or on any other part of the kernel, so
+//! this module also compiles for the host. Its tests run there, using the `host_tests` package:
+//!
+//! ```console
+//! $ make test_unit
+//! ```
+
+use core::{
+    num::{NonZeroU128, NonZeroU32, NonZeroU64},
+    ops::Div,
+    time::Duration,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const NANOSEC_PER_SEC: NonZeroU64 = NonZeroU64::new(1_000_000_000).unwrap();
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+/// Convert a counter value to a `Duration`, rounding down to whole nanoseconds.
+pub fn to_duration(counter_value: u64, frequency: NonZeroU32) -> Duration {
+    if counter_value == 0 {
+        return Duration::ZERO;
+    }
+
+    let frequency: NonZeroU64 = frequency.into();
+
+    // Div<NonZeroU64> implementation for u64 cannot panic.
+    let secs = counter_value.div(frequency);
+
+    // The frequency is at most u32::MAX, so sub_second_counter_value is at most (u32::MAX - 1).
+    // Multiplied with NANOSEC_PER_SEC, this is still smaller than u64::MAX.
+    //
+    // The result of the division is smaller than NANOSEC_PER_SEC, so it fits into an u32.
+    let sub_second_counter_value = counter_value % frequency;
+    let nanos = (sub_second_counter_value * u64::from(NANOSEC_PER_SEC)).div(frequency) as u32;
+
+    Duration::new(secs, nanos)
+}
+
+/// The timer's resolution.
+pub fn resolution(frequency: NonZeroU32) -> Duration {
+    to_duration(1, frequency)
+}
+
+/// The largest `Duration` that a counter value can represent.
+pub fn max_duration(frequency: NonZeroU32) -> Duration {
+    to_duration(u64::MAX, frequency)
+}
+
+/// Convert a `Duration` to a counter value, rounding down to whole counter ticks.
+///
+/// Returns `None` if the duration is larger than [`max_duration()`].
+pub fn from_duration(duration: Duration, frequency: NonZeroU32) -> Option<u64> {
+    if duration < resolution(frequency) {
+        return Some(0);
+    }
+
+    if duration > max_duration(frequency) {
+        return None;
+    }
+
+    let frequency = u128::from(u32::from(frequency));
+    let duration: u128 = duration.as_nanos();
+
+    // Duration::MAX.as_nanos() is below 2^94 and the frequency below 2^32, so the product cannot
+    // overflow an u128.
+    let counter_value = (duration * frequency).div(NonZeroU128::from(NANOSEC_PER_SEC));
+
+    // Since the duration is at most max_duration(), the result fits into an u64.
+    Some(counter_value as u64)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUM_CASES: usize = 100_000;
+
+    /// Frequencies of real hardware, and the extremes.
+    const FREQUENCIES: [u32; 6] = [1, 1_000, 19_200_000, 54_000_000, 1_000_000_000, u32::MAX];
+
+    /// A xorshift PRNG, so that the property tests are reproducible and need no dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// A value with a random number of significant bits, so that small values are common.
+        fn next_scaled(&mut self) -> u64 {
+            let bits = self.next() % 65;
+            self.next().checked_shr(64 - bits as u32).unwrap_or(0)
+        }
+
+        fn frequency(&mut self) -> NonZeroU32 {
+            let frequency = match self.next() % 4 {
+                0 => FREQUENCIES[self.next() as usize % FREQUENCIES.len()],
+                _ => self.next_scaled() as u32,
+            };
+
+            NonZeroU32::new(frequency).unwrap_or(NonZeroU32::MIN)
+        }
+
+        fn duration(&mut self) -> Duration {
+            Duration::new(
+                self.next_scaled(),
+                (self.next() % NANOSEC_PER_SEC.get()) as u32,
+            )
+        }
+    }
+
+    fn freq(frequency: u32) -> NonZeroU32 {
+        NonZeroU32::new(frequency).unwrap()
+    }
+
+    /// The exact number of nanoseconds of a counter value, computed without any shortcuts.
+    fn exact_nanos(counter_value: u64, frequency: NonZeroU32) -> u128 {
+        u128::from(counter_value) * 1_000_000_000 / u128::from(frequency.get())
+    }
+
+    #[test]
+    fn to_duration_known_values() {
+        assert_eq!(to_duration(0, freq(1)), Duration::ZERO);
+        assert_eq!(to_duration(1, freq(1)), Duration::from_secs(1));
+        assert_eq!(
+            to_duration(54_000_000, freq(54_000_000)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(to_duration(54, freq(54_000_000)), Duration::from_micros(1));
+        assert_eq!(
+            to_duration(19_200_001, freq(19_200_000)),
+            Duration::new(1, 52)
+        );
+        assert_eq!(
+            to_duration(u64::MAX, freq(1)),
+            Duration::from_secs(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn from_duration_known_values() {
+        assert_eq!(from_duration(Duration::ZERO, freq(1)), Some(0));
+        assert_eq!(
+            from_duration(Duration::from_secs(1), freq(54_000_000)),
+            Some(54_000_000)
+        );
+        assert_eq!(
+            from_duration(Duration::from_micros(1), freq(54_000_000)),
+            Some(54)
+        );
+        assert_eq!(
+            from_duration(Duration::from_millis(10), freq(19_200_000)),
+            Some(192_000)
+        );
+        assert_eq!(from_duration(Duration::MAX, freq(1)), None);
+    }
+
+    #[test]
+    fn resolution_is_one_tick() {
+        assert_eq!(resolution(freq(1)), Duration::from_secs(1));
+        assert_eq!(resolution(freq(1_000)), Duration::from_millis(1));
+        assert_eq!(resolution(freq(54_000_000)), Duration::from_nanos(18));
+
+        // Above 1 GHz, a tick is shorter than a nanosecond.
+        assert_eq!(resolution(freq(1_000_000_000)), Duration::from_nanos(1));
+        assert_eq!(resolution(freq(u32::MAX)), Duration::ZERO);
+    }
+
+    #[test]
+    fn sub_resolution_is_zero() {
+        for frequency in FREQUENCIES.map(freq) {
+            let resolution = resolution(frequency);
+            if resolution.is_zero() {
+                continue;
+            }
+
+            assert_eq!(
+                from_duration(resolution - Duration::from_nanos(1), frequency),
+                Some(0)
+            );
+        }
+
+        // The resolution itself is rounded down, so it may convert back to zero ticks.
+        assert_eq!(
+            from_duration(resolution(freq(54_000_000)), freq(54_000_000)),
+            Some(0)
+        );
+        assert_eq!(from_duration(resolution(freq(1_000)), freq(1_000)), Some(1));
+    }
+
+    #[test]
+    fn max_duration_edge() {
+        for frequency in FREQUENCIES.map(freq) {
+            let max = max_duration(frequency);
+
+            assert_eq!(max.as_nanos(), exact_nanos(u64::MAX, frequency));
+            assert!(from_duration(max, frequency).is_some());
+            assert_eq!(
+                from_duration(max + Duration::from_nanos(1), frequency),
+                None
+            );
+        }
+
+        // At 1 Hz, every counter value is a whole number of seconds, so nothing is rounded.
+        assert_eq!(max_duration(freq(1)), Duration::from_secs(u64::MAX));
+        assert_eq!(
+            from_duration(max_duration(freq(1)), freq(1)),
+            Some(u64::MAX)
+        );
+    }
+
+    #[test]
+    fn prop_to_duration_is_exact() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..NUM_CASES {
+            let frequency = rng.frequency();
+            let counter_value = rng.next_scaled();
+
+            assert_eq!(
+                to_duration(counter_value, frequency).as_nanos(),
+                exact_nanos(counter_value, frequency),
+                "counter value {counter_value} at {frequency} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn prop_from_duration_rounds_down_to_ticks() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..NUM_CASES {
+            let frequency = rng.frequency();
+            let duration = rng.duration();
+
+            match from_duration(duration, frequency) {
+                None => assert!(duration > max_duration(frequency)),
+                Some(counter_value) => {
+                    // The result is the largest counter value that is not longer than the duration.
+                    assert!(to_duration(counter_value, frequency) <= duration);
+                    if let Some(next) = counter_value.checked_add(1) {
+                        assert!(
+                            to_duration(next, frequency) >= duration,
+                            "{duration:?} at {frequency} Hz"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prop_round_trip_loses_at_most_a_nanosecond() {
+        let mut rng = Rng(0xda94_2042_e4dd_58b5);
+
+        for _ in 0..NUM_CASES {
+            let frequency = rng.frequency();
+            let counter_value = rng.next_scaled();
+
+            let round_trip = from_duration(to_duration(counter_value, frequency), frequency)
+                .expect("max_duration() always converts back");
+
+            // Both directions round down. The loss is less than one tick plus the ticks that fit
+            // into the nanosecond cut off by to_duration(), which is at most one below 1 GHz.
+            assert!(round_trip <= counter_value);
+            assert!(
+                u128::from(counter_value - round_trip) * 1_000_000_000
+                    < u128::from(frequency.get()) + 1_000_000_000,
+                "counter value {counter_value} at {frequency} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn prop_monotonic() {
+        let mut rng = Rng(0x1234_5678_9abc_def1);
+
+        for _ in 0..NUM_CASES {
+            let frequency = rng.frequency();
+            let (a, b) = (rng.next_scaled(), rng.next_scaled());
+            let (a, b) = (a.min(b), a.max(b));
+
+            assert!(to_duration(a, frequency) <= to_duration(b, frequency));
+
+            let (a, b) = (to_duration(a, frequency), to_duration(b, frequency));
+            assert!(from_duration(a, frequency) <= from_duration(b, frequency));
+        }
+    }
+}
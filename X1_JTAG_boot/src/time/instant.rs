@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Points in time as raw counter values.
+//!
+//! Reading the counter and converting ticks to `Duration` is up to the architecture. The
+//! wrap-around safe arithmetic on raw counter values does not depend on it, so this module also
+//! compiles for the host. Its tests run there, using the `host_tests` package.
+
+use core::cmp::Ordering;
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// A point in time, as a raw value of the monotonic system counter.
+///
+/// Comparing and subtracting instants is a plain integer operation on the counter values, so
+/// polling against a deadline is cheap. Both handle a wrap-around of the counter, as long as the
+/// instants involved are less than half of the counter range apart. Even at the highest possible
+/// counter frequency, that is more than 68 years.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Instant(u64);
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl Instant {
+    /// The largest number of ticks between two instants that still orders them correctly.
+    pub const MAX_DELTA: u64 = i64::MAX as u64;
+
+    /// Create an instance from a raw counter value.
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self(ticks)
+    }
+
+    /// Return the raw counter value.
+    pub const fn ticks(&self) -> u64 {
+        self.0
+    }
+
+    /// Return the number of ticks from `earlier` to `self`, or zero if `earlier` is later.
+    pub fn ticks_since(&self, earlier: Instant) -> u64 {
+        if *self <= earlier {
+            return 0;
+        }
+
+        self.0.wrapping_sub(earlier.0)
+    }
+
+    /// Return the instant `ticks` after `self`, or `None` if it is too far away to be ordered
+    /// correctly.
+    pub fn checked_add_ticks(&self, ticks: u64) -> Option<Instant> {
+        if ticks > Self::MAX_DELTA {
+            return None;
+        }
+
+        Some(Self(self.0.wrapping_add(ticks)))
+    }
+}
+
+impl PartialOrd for Instant {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Instant {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Interpreting the wrapping difference as signed makes the comparison wrap-around safe.
+        (self.0.wrapping_sub(other.0) as i64).cmp(&0)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ticks: u64) -> Instant {
+        Instant::from_ticks(ticks)
+    }
+
+    #[test]
+    fn order_across_wrap() {
+        assert!(at(1) < at(2));
+        assert!(at(u64::MAX) < at(0));
+        assert!(at(u64::MAX - 5) < at(5));
+        assert_eq!(at(7).cmp(&at(7)), Ordering::Equal);
+
+        // More than half the range apart, the order flips.
+        assert!(at(0) < at(Instant::MAX_DELTA));
+        assert!(at(0) > at(Instant::MAX_DELTA + 2));
+    }
+
+    #[test]
+    fn ticks_since_across_wrap() {
+        assert_eq!(at(10).ticks_since(at(3)), 7);
+        assert_eq!(at(2).ticks_since(at(u64::MAX - 1)), 4);
+
+        // The earlier instant is later.
+        assert_eq!(at(3).ticks_since(at(10)), 0);
+        assert_eq!(at(u64::MAX).ticks_since(at(0)), 0);
+        assert_eq!(at(3).ticks_since(at(3)), 0);
+    }
+
+    #[test]
+    fn checked_add_ticks_wraps() {
+        assert_eq!(at(1).checked_add_ticks(2), Some(at(3)));
+        assert_eq!(at(u64::MAX).checked_add_ticks(3), Some(at(2)));
+
+        let latest = at(u64::MAX - 1)
+            .checked_add_ticks(Instant::MAX_DELTA)
+            .unwrap();
+        assert!(latest > at(u64::MAX - 1));
+        assert_eq!(latest.ticks_since(at(u64::MAX - 1)), Instant::MAX_DELTA);
+
+        assert_eq!(at(0).checked_add_ticks(Instant::MAX_DELTA + 1), None);
+    }
+}
@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+//
+// Copyright (c) 2022-2023 Andre Richter <andre.o.richter@gmail.com>
+
+//! Queue of pending timeouts.
+//!
+//! Holds up to `MAX_TIMEOUTS` timeouts, sorted by deadline. Timeouts with the same deadline expire
+//! in the order they were added.
+//!
+//! Deadlines and periods are raw counter values, and callbacks can be of any type, so this module
+//! does not depend on the architecture. Its tests run on the host, using the `host_tests` package.
+
+use super::Instant;
+
+//--------------------------------------------------------------------------------------------------
+// Private Definitions
+//--------------------------------------------------------------------------------------------------
+
+const MAX_TIMEOUTS: usize = 8;
+
+#[derive(Copy, Clone)]
+struct Timeout<C> {
+    due: Instant,
+    period: Option<u64>,
+    callback: C,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Public Definitions
+//--------------------------------------------------------------------------------------------------
+
+/// Pending timeouts, earliest deadline first.
+pub struct TimeoutQueue<C> {
+    /// Sorted by deadline. All `None` entries are at the end.
+    entries: [Option<Timeout<C>>; MAX_TIMEOUTS],
+}
+
+/// All slots of the queue are taken.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct QueueFull;
+
+//--------------------------------------------------------------------------------------------------
+// Public Code
+//--------------------------------------------------------------------------------------------------
+
+impl<C: Copy> TimeoutQueue<C> {
+    /// Create an instance.
+    pub const fn new() -> Self {
+        Self {
+            entries: [None; MAX_TIMEOUTS],
+        }
+    }
+
+    /// The deadline of the earliest timeout, if any.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.entries[0].map(|x| x.due)
+    }
+
+    /// Add a timeout that expires at `due`, and again every `period` counter ticks afterwards if
+    /// given.
+    pub fn add(&mut self, due: Instant, period: Option<u64>, callback: C) -> Result<(), QueueFull> {
+        if self.entries[MAX_TIMEOUTS - 1].is_some() {
+            return Err(QueueFull);
+        }
+
+        let i = self
+            .entries
+            .iter()
+            .position(|x| !matches!(x, Some(x) if x.due <= due))
+            .unwrap_or(MAX_TIMEOUTS - 1);
+
+        self.entries[i..].rotate_right(1);
+        self.entries[i] = Some(Timeout {
+            due,
+            period,
+            callback,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the earliest timeout if it expired at or before `now`, and return its callback.
+    ///
+    /// Periodic timeouts are added again for their next deadline after `now`. Hence, each timeout
+    /// is returned at most once for the same `now`.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<C> {
+        let timeout = self.entries[0].filter(|x| x.due <= now)?;
+
+        self.entries[0] = None;
+        self.entries.rotate_left(1);
+
+        if let Some(period) = timeout.period {
+            // If deadlines were missed, skip them instead of firing repeatedly to catch up.
+            let next_due = timeout
+                .due
+                .checked_add_ticks(period)
+                .filter(|x| *x > now)
+                .or_else(|| now.checked_add_ticks(period));
+
+            // Cannot fail, because the slot of the popped timeout is free again.
+            if let Some(next_due) = next_due {
+                let _ = self.add(next_due, Some(period), timeout.callback);
+            }
+        }
+
+        Some(timeout.callback)
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Testing
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ticks: u64) -> Instant {
+        Instant::from_ticks(ticks)
+    }
+
+    /// Pop everything that expired at `now`.
+    fn expired(queue: &mut TimeoutQueue<char>, now: u64) -> String {
+        core::iter::from_fn(|| queue.pop_expired(at(now))).collect()
+    }
+
+    #[test]
+    fn sorted_by_deadline() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(30), None, 'c').unwrap();
+        queue.add(at(10), None, 'a').unwrap();
+        queue.add(at(20), None, 'b').unwrap();
+
+        assert_eq!(queue.next_due(), Some(at(10)));
+        assert_eq!(expired(&mut queue, 9), "");
+        assert_eq!(expired(&mut queue, 20), "ab");
+        assert_eq!(queue.next_due(), Some(at(30)));
+        assert_eq!(expired(&mut queue, 100), "c");
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn equal_deadlines_in_insertion_order() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(10), None, 'a').unwrap();
+        queue.add(at(5), None, 'x').unwrap();
+        queue.add(at(10), None, 'b').unwrap();
+        queue.add(at(10), None, 'c').unwrap();
+
+        assert_eq!(expired(&mut queue, 10), "xabc");
+    }
+
+    #[test]
+    fn deadlines_across_counter_wrap() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(2), None, 'b').unwrap();
+        queue.add(at(u64::MAX), None, 'a').unwrap();
+
+        assert_eq!(expired(&mut queue, u64::MAX), "a");
+        assert_eq!(expired(&mut queue, 2), "b");
+    }
+
+    #[test]
+    fn full_queue_is_rejected() {
+        let mut queue = TimeoutQueue::new();
+        for i in 0..MAX_TIMEOUTS {
+            queue.add(at(i as u64), None, 'a').unwrap();
+        }
+
+        assert_eq!(queue.add(at(0), None, 'b'), Err(QueueFull));
+
+        // Nothing was lost or replaced.
+        assert_eq!(expired(&mut queue, 100), "a".repeat(MAX_TIMEOUTS));
+    }
+
+    #[test]
+    fn periodic_timeout_is_added_again() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(10), Some(10), 'p').unwrap();
+
+        assert_eq!(expired(&mut queue, 10), "p");
+        assert_eq!(queue.next_due(), Some(at(20)));
+        assert_eq!(expired(&mut queue, 25), "p");
+        assert_eq!(queue.next_due(), Some(at(30)));
+    }
+
+    #[test]
+    fn periodic_timeout_skips_missed_deadlines() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(10), Some(10), 'p').unwrap();
+
+        // Deadlines 10 to 50 were missed. Fire once, then wait a full period.
+        assert_eq!(expired(&mut queue, 55), "p");
+        assert_eq!(queue.next_due(), Some(at(65)));
+    }
+
+    #[test]
+    fn periodic_timeout_in_full_queue() {
+        let mut queue = TimeoutQueue::new();
+        queue.add(at(10), Some(10), 'p').unwrap();
+        for _ in 1..MAX_TIMEOUTS {
+            queue.add(at(100), None, 'a').unwrap();
+        }
+
+        assert_eq!(expired(&mut queue, 10), "p");
+        assert_eq!(queue.next_due(), Some(at(20)));
+    }
+}